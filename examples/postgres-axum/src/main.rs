@@ -43,10 +43,14 @@ async fn main() {
     let pool = qp_postgres::connect(config, NoTls, 8);
     let app = Router::new()
         .route("/", get(add_number))
-        .layer(Extension(pool));
+        .layer(Extension(pool.clone()));
     let addr = SERVER_ADDRESS.parse().unwrap();
     Server::bind(&addr)
         .serve(app.into_make_service())
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.unwrap();
+        })
         .await
         .unwrap();
+    pool.drain().await;
 }
@@ -11,18 +11,25 @@ async fn main() {
     let pool = qp_postgres::connect(config, NoTls, 8);
     let addr = SERVER_ADDRESS.parse().unwrap();
     Server::bind(&addr)
-        .serve(make_service_fn(move |_| {
+        .serve(make_service_fn({
             let pool = pool.clone();
-            async move {
-                Ok::<_, Error>(service_fn(move |_req| {
-                    let pool = pool.clone();
-                    async move {
-                        let _client = pool.acquire().await.unwrap();
-                        Ok::<_, Error>(Response::new(Body::from("ok")))
-                    }
-                }))
+            move |_| {
+                let pool = pool.clone();
+                async move {
+                    Ok::<_, Error>(service_fn(move |_req| {
+                        let pool = pool.clone();
+                        async move {
+                            let _client = pool.acquire().await.unwrap();
+                            Ok::<_, Error>(Response::new(Body::from("ok")))
+                        }
+                    }))
+                }
             }
         }))
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.unwrap();
+        })
         .await
         .unwrap();
+    pool.drain().await;
 }
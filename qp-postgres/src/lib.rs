@@ -14,7 +14,7 @@ pub type PgPool<T> = Pool<PgConnManager<T>>;
 /// A PostgreSQL connection manager.
 pub struct PgConnManager<T>
 where
-    T: MakeTlsConnect<Socket> + Clone + Send + Sync,
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
     T::Stream: Send + Sync + 'static,
     T::TlsConnect: Send + Sync,
     <T::TlsConnect as TlsConnect<Socket>>::Future: Send,
@@ -26,7 +26,7 @@ where
 #[async_trait]
 impl<T> Manage for PgConnManager<T>
 where
-    T: MakeTlsConnect<Socket> + Clone + Send + Sync,
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
     T::Stream: Send + Sync + 'static,
     T::TlsConnect: Send + Sync,
     <T::TlsConnect as TlsConnect<Socket>>::Future: Send,
@@ -47,7 +47,7 @@ where
 
 impl<T> PgConnManager<T>
 where
-    T: MakeTlsConnect<Socket> + Clone + Send + Sync,
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
     T::Stream: Send + Sync + 'static,
     T::TlsConnect: Send + Sync,
     <T::TlsConnect as TlsConnect<Socket>>::Future: Send,
@@ -61,7 +61,7 @@ where
 /// Creates a new PostgreSQL connection pool.
 pub fn connect<T>(config: Config, tls: T, pool_size: usize) -> PgPool<T>
 where
-    T: MakeTlsConnect<Socket> + Clone + Send + Sync,
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
     T::Stream: Send + Sync + 'static,
     T::TlsConnect: Send + Sync,
     <T::TlsConnect as TlsConnect<Socket>>::Future: Send,
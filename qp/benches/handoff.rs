@@ -0,0 +1,104 @@
+//! Compares the resources created under bursty load with and without handoff.
+//!
+//! Every burst spawns many more tasks than the pool has resources, so most of them park until a
+//! resource is returned, while the replenisher keeps the pool topped up to `min_idle`. With
+//! handoff, a returned resource is handed over to a parked task together with its slot. Without
+//! it, the resource is put back into the pool for the task to take when it wakes up. Neither
+//! creates more than `max_size` resources.
+//!
+//! Run it with:
+//!
+//! ```sh
+//! cargo bench -p qp --bench handoff
+//! ```
+use qp::resource::Manage;
+use qp::{async_trait, Pool, QueueStrategy};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+const MAX_SIZE: usize = 16;
+const TASKS: usize = 256;
+const BURSTS: usize = 100;
+
+#[derive(Default)]
+struct Manager {
+    created: AtomicUsize,
+}
+
+#[async_trait]
+impl Manage for Manager {
+    type Output = ();
+    type Error = ();
+
+    async fn try_create(&self) -> Result<Self::Output, Self::Error> {
+        self.created.fetch_add(1, Ordering::Relaxed);
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        Ok(())
+    }
+
+    async fn validate(&self, _resource: &Self::Output) -> bool {
+        tokio::task::yield_now().await;
+        true
+    }
+}
+
+/// Returns the number of resources created and the elapsed time.
+async fn run(fair: bool, queue_strategy: QueueStrategy, handoff: bool) -> (usize, Duration) {
+    let pool = Pool::builder(Manager::default())
+        .max_size(MAX_SIZE)
+        .fair(fair)
+        .queue_strategy(queue_strategy)
+        .handoff(handoff)
+        // The replenisher competes with the parked tasks for every returned slot.
+        .min_idle(MAX_SIZE)
+        .build()
+        .unwrap();
+    let start = Instant::now();
+    for _ in 0..BURSTS {
+        let tasks: Vec<_> = (0..TASKS)
+            .map(|_| {
+                let pool = pool.clone();
+                tokio::spawn(async move {
+                    let _resource = pool.acquire().await.unwrap();
+                    tokio::task::yield_now().await;
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+    }
+    let elapsed = start.elapsed();
+    assert_eq!(pool.counters().acquires, (TASKS * BURSTS) as u64);
+    let created = pool.manager().created.load(Ordering::Relaxed);
+    assert!(created <= MAX_SIZE);
+    (created, elapsed)
+}
+
+#[tokio::main]
+async fn main() {
+    println!(
+        "{} bursts of {} tasks, max size {}",
+        BURSTS, TASKS, MAX_SIZE
+    );
+    println!("{:11} | {:^20} | {:^20}", "", "baseline", "handoff");
+    println!(
+        "{:5} {:5} | {:>9} {:>10} | {:>9} {:>10}",
+        "fair", "queue", "created", "elapsed", "created", "elapsed"
+    );
+    for fair in [false, true] {
+        for queue_strategy in [QueueStrategy::Fifo, QueueStrategy::Lifo] {
+            let (baseline, baseline_elapsed) = run(fair, queue_strategy, false).await;
+            let (handoff, handoff_elapsed) = run(fair, queue_strategy, true).await;
+            println!(
+                "{:5} {:5} | {:9} {:>10} | {:9} {:>10}",
+                fair,
+                format!("{:?}", queue_strategy),
+                baseline,
+                format!("{:.2?}", baseline_elapsed),
+                handoff,
+                format!("{:.2?}", handoff_elapsed)
+            );
+        }
+    }
+}
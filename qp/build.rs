@@ -0,0 +1,6 @@
+fn main() {
+    // Declares the `loom` cfg used by the model tests without a `[lints]` table, which would
+    // require Cargo 1.74.
+    println!("cargo:rustc-check-cfg=cfg(loom)");
+    println!("cargo:rerun-if-changed=build.rs");
+}
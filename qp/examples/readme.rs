@@ -57,7 +57,6 @@ async fn main() {
     // take the resource from the pool.
     let raw_int: i32 = Pooled::take(int); // raw resource
     dbg!(raw_int); // 0
-    drop(raw_int);
 
     let _int = pool.acquire().await.unwrap();
     // `_int` will be auto released by `Pooled` destructor.
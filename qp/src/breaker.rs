@@ -0,0 +1,152 @@
+use crate::builder::CircuitBreaker;
+use crate::loom::{AtomicU64, AtomicUsize};
+use crate::status::BreakerState;
+use std::mem;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::time::Instant;
+
+const CLOSED: usize = 0;
+const OPEN: usize = 1;
+const HALF_OPEN: usize = 2;
+
+/// Tracks consecutive creation failures and stops creations while the backend seems down.
+pub(crate) struct Breaker {
+    config: Option<CircuitBreaker>,
+    state: AtomicUsize,
+    failures: AtomicUsize,
+    /// The time the cool-down ends, in nanoseconds since `base`.
+    open_until: AtomicU64,
+    base: Instant,
+}
+
+impl Breaker {
+    pub(crate) fn new(config: Option<CircuitBreaker>) -> Self {
+        Self {
+            config,
+            state: AtomicUsize::new(CLOSED),
+            failures: AtomicUsize::new(0),
+            open_until: AtomicU64::new(0),
+            base: Instant::now(),
+        }
+    }
+
+    /// Returns an [`Attempt`] if a creation may be started, or `None` if the breaker is open.
+    ///
+    /// Once the cool-down has ended, a single caller is let through as a probe.
+    pub(crate) fn admit(&self) -> Option<Attempt<'_>> {
+        let cooled_down = || self.now() >= self.open_until.load(Ordering::Relaxed);
+        let probe = match self.state.load(Ordering::Acquire) {
+            CLOSED => false,
+            OPEN if cooled_down() => {
+                let state = self.state.compare_exchange(
+                    OPEN,
+                    HALF_OPEN,
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                );
+                if state.is_err() {
+                    return None;
+                }
+                true
+            }
+            _ => return None,
+        };
+        Some(Attempt {
+            breaker: self,
+            probe,
+        })
+    }
+
+    pub(crate) fn state(&self) -> BreakerState {
+        match self.state.load(Ordering::Acquire) {
+            CLOSED => BreakerState::Closed,
+            OPEN => BreakerState::Open,
+            _ => BreakerState::HalfOpen,
+        }
+    }
+
+    fn open(&self, cool_down: Duration) {
+        let nanos = u64::try_from(cool_down.as_nanos()).unwrap_or(u64::MAX);
+        let open_until = self.now().saturating_add(nanos);
+        self.open_until.store(open_until, Ordering::Relaxed);
+        self.state.store(OPEN, Ordering::Release);
+    }
+
+    fn now(&self) -> u64 {
+        u64::try_from(self.base.elapsed().as_nanos()).unwrap_or(u64::MAX)
+    }
+}
+
+/// A creation admitted by [`Breaker::admit`].
+///
+/// Dropping a probe without recording its outcome reopens the breaker, letting the next caller
+/// probe at once.
+pub(crate) struct Attempt<'a> {
+    breaker: &'a Breaker,
+    probe: bool,
+}
+
+impl Attempt<'_> {
+    pub(crate) fn succeeded(self) {
+        let breaker = self.breaker;
+        mem::forget(self);
+        breaker.failures.store(0, Ordering::Relaxed);
+        breaker.state.store(CLOSED, Ordering::Release);
+    }
+
+    pub(crate) fn failed(self) {
+        let breaker = self.breaker;
+        let probe = self.probe;
+        mem::forget(self);
+        let failures = breaker.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some(config) = &breaker.config {
+            let tripped = failures >= config.failure_threshold as usize
+                && breaker.state.load(Ordering::Acquire) == CLOSED;
+            if probe || tripped {
+                breaker.open(config.cool_down);
+            }
+        }
+    }
+}
+
+impl Drop for Attempt<'_> {
+    fn drop(&mut self) {
+        if self.probe {
+            self.breaker.open(Duration::ZERO);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_breaker() {
+        let breaker = Breaker::new(Some(CircuitBreaker {
+            failure_threshold: 2,
+            cool_down: Duration::from_secs(10),
+        }));
+        breaker.admit().unwrap().failed();
+        breaker.admit().unwrap().succeeded();
+        breaker.admit().unwrap().failed();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        breaker.admit().unwrap().failed();
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(breaker.admit().is_none());
+
+        // A single probe is let through after the cool-down.
+        tokio::time::advance(Duration::from_secs(10)).await;
+        let probe = breaker.admit().unwrap();
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        assert!(breaker.admit().is_none());
+        probe.failed();
+        assert!(breaker.admit().is_none());
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        drop(breaker.admit().unwrap());
+        breaker.admit().unwrap().succeeded();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+    }
+}
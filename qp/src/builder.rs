@@ -0,0 +1,530 @@
+use crate::error::ConfigError;
+use crate::event::{Event, Hook};
+use crate::pool::Pool;
+use crate::resource::Manage;
+use std::time::Duration;
+
+/// The settings of [`Pool`].
+///
+/// With the `serde` feature enabled, `PoolConfig` can be deserialized from any format supported
+/// by `serde`, and missing fields fall back to their default values. Durations are written as
+/// strings such as `"5s"`, `"250ms"` or `"1m 30s"`.
+///
+/// # Examples
+///
+/// ```
+/// # use qp::PoolConfig;
+/// # use std::time::Duration;
+/// let config = PoolConfig {
+///     max_size: 16,
+///     wait_timeout: Some(Duration::from_secs(5)),
+///     ..PoolConfig::default()
+/// };
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct PoolConfig {
+    /// The maximum number of resources the pool can manage.
+    pub max_size: usize,
+    /// The minimum number of idle resources the pool tries to keep.
+    pub min_idle: usize,
+    /// The maximum duration to wait for a resource in [`Pool::acquire`] and related methods.
+    #[cfg_attr(feature = "serde", serde(with = "crate::duration::option"))]
+    pub wait_timeout: Option<Duration>,
+    /// The maximum duration a resource may stay idle in the pool.
+    #[cfg_attr(feature = "serde", serde(with = "crate::duration::option"))]
+    pub idle_timeout: Option<Duration>,
+    /// The maximum lifetime of a resource.
+    #[cfg_attr(feature = "serde", serde(with = "crate::duration::option"))]
+    pub max_lifetime: Option<Duration>,
+    /// Whether resources are handed out strictly in the order they were requested.
+    ///
+    /// See [`Semaphore::new_fair`](crate::sync::Semaphore::new_fair).
+    pub fair: bool,
+    /// The order in which idle resources are handed out.
+    pub queue_strategy: QueueStrategy,
+    /// Whether a returned resource is handed directly to a task waiting for one, instead of being
+    /// put back into the pool for the task to take when it wakes up. Disabled by default.
+    pub handoff: bool,
+    /// When resources are validated before they are acquired.
+    pub validation_policy: ValidationPolicy,
+    /// Whether newly created resources are validated before they are handed out or kept idle.
+    pub validate_created: bool,
+    /// Whether acquisitions fail with [`PoolError::Invalid`](crate::PoolError::Invalid) instead of
+    /// the creation error if every idle resource failed
+    /// [`Manage::check`](crate::resource::Manage::check) and a new one could not be created.
+    pub report_invalid: bool,
+    /// How failed resource creations are retried while acquiring a resource.
+    pub create_retry: RetryPolicy,
+    /// The circuit breaker which stops resource creations after repeated failures, if any.
+    pub circuit_breaker: Option<CircuitBreaker>,
+    /// The maximum number of resources created at the same time, if any.
+    ///
+    /// Tasks which cannot start a creation wait until either a resource is returned to the pool
+    /// or another creation finishes.
+    pub max_concurrent_creates: Option<usize>,
+    /// The maximum duration of each [`Manage::try_create`], [`Manage::recycle`] and validation
+    /// call, if any.
+    ///
+    /// A creation which times out emits [`Event::CreateTimedOut`](crate::Event::CreateTimedOut)
+    /// and fails with [`PoolError::CreateTimeout`](crate::PoolError::CreateTimeout), while a
+    /// resource whose recycling or validation times out is destroyed. Unlike `wait_timeout`, this
+    /// also bounds the creations of the replenisher.
+    #[cfg_attr(feature = "serde", serde(with = "crate::duration::option"))]
+    pub create_timeout: Option<Duration>,
+}
+
+impl PoolConfig {
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.max_size == 0 {
+            return Err(ConfigError::ZeroMaxSize);
+        }
+        if self.min_idle > self.max_size {
+            return Err(ConfigError::MinIdleAboveMaxSize);
+        }
+        if self.max_concurrent_creates == Some(0) {
+            return Err(ConfigError::ZeroMaxConcurrentCreates);
+        }
+        Ok(())
+    }
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_size: 10,
+            min_idle: 0,
+            wait_timeout: None,
+            idle_timeout: None,
+            max_lifetime: None,
+            fair: false,
+            queue_strategy: QueueStrategy::default(),
+            handoff: false,
+            validation_policy: ValidationPolicy::default(),
+            validate_created: false,
+            report_invalid: false,
+            create_retry: RetryPolicy::default(),
+            circuit_breaker: None,
+            max_concurrent_creates: None,
+            create_timeout: None,
+        }
+    }
+}
+
+/// The order in which [`Pool`] hands out idle resources.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum QueueStrategy {
+    /// Hands out the resource which has been idle the longest, so that all resources wear evenly
+    /// and stay warm.
+    Fifo,
+    /// Hands out the most recently returned resource, so that the rarely used ones exceed the
+    /// idle timeout and the pool shrinks back after a burst.
+    Lifo,
+}
+
+impl Default for QueueStrategy {
+    fn default() -> Self {
+        Self::Fifo
+    }
+}
+
+/// How [`Pool`] retries [`Manage::try_create`] when it fails.
+///
+/// After each failed attempt, the pool waits for a random duration between half of the backoff
+/// and the backoff, which starts at `initial_backoff` and doubles up to `max_backoff`. The error of
+/// the last attempt is returned if all of them fail.
+///
+/// The default policy makes a single attempt.
+///
+/// # Examples
+///
+/// ```
+/// # use qp::RetryPolicy;
+/// # use std::time::Duration;
+/// let policy = RetryPolicy {
+///     max_attempts: 5,
+///     deadline: Some(Duration::from_secs(10)),
+///     ..RetryPolicy::default()
+/// };
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first one.
+    pub max_attempts: u32,
+    /// The backoff after the first failed attempt.
+    #[cfg_attr(feature = "serde", serde(with = "crate::duration"))]
+    pub initial_backoff: Duration,
+    /// The maximum backoff between two attempts.
+    #[cfg_attr(feature = "serde", serde(with = "crate::duration"))]
+    pub max_backoff: Duration,
+    /// The duration since the first attempt after which no more attempts are started. An attempt
+    /// still running at the deadline is abandoned and fails like one exceeding the create timeout.
+    #[cfg_attr(feature = "serde", serde(with = "crate::duration::option"))]
+    pub deadline: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            deadline: None,
+        }
+    }
+}
+
+/// The settings of the circuit breaker around [`Manage::try_create`].
+///
+/// After `failure_threshold` consecutive creation failures, the breaker opens and acquisitions
+/// which need a new resource fail immediately with
+/// [`PoolError::Unavailable`](crate::PoolError::Unavailable). Once `cool_down` has elapsed, a
+/// single creation is attempted as a probe, which closes the breaker if it succeeds and reopens it
+/// otherwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct CircuitBreaker {
+    /// The number of consecutive creation failures which opens the breaker.
+    pub failure_threshold: u32,
+    /// The duration the breaker stays open before a probe creation is attempted.
+    #[cfg_attr(feature = "serde", serde(with = "crate::duration"))]
+    pub cool_down: Duration,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cool_down: Duration::from_secs(30),
+        }
+    }
+}
+
+/// When [`Pool`] calls [`Manage::validate`] on a resource before handing it out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum ValidationPolicy {
+    /// Validates every resource before it is acquired.
+    Always,
+    /// Never validates resources.
+    Never,
+    /// Validates resources which have not been used for longer than the given duration.
+    IfIdleLongerThan(#[cfg_attr(feature = "serde", serde(with = "crate::duration"))] Duration),
+    /// Validates resources every `n` times they are acquired.
+    ///
+    /// `EveryNthUse(0)` never validates resources.
+    EveryNthUse(u64),
+}
+
+impl Default for ValidationPolicy {
+    fn default() -> Self {
+        Self::Always
+    }
+}
+
+/// A builder for [`Pool`].
+///
+/// This type is created by the [`Pool::builder`] method.
+///
+/// # Examples
+///
+/// ```
+/// # use qp::resource::Manage;
+/// # use qp::{async_trait, Pool};
+/// # use std::time::Duration;
+/// # struct IntManager;
+/// # #[async_trait]
+/// # impl Manage for IntManager {
+/// #     type Output = i32;
+/// #     type Error = ();
+/// #     async fn try_create(&self) -> Result<Self::Output, Self::Error> {
+/// #         Ok(0)
+/// #     }
+/// # }
+/// # #[tokio::main]
+/// # async fn main() {
+/// let pool = Pool::builder(IntManager)
+///     .max_size(4)
+///     .wait_timeout(Some(Duration::from_secs(5)))
+///     .hook(|event| println!("{:?}", event))
+///     .build()
+///     .unwrap();
+/// assert_eq!(pool.max_size(), 4);
+/// # }
+/// ```
+pub struct PoolBuilder<M: Manage> {
+    manager: M,
+    config: PoolConfig,
+    hooks: Vec<Hook<M::Error>>,
+}
+
+impl<M: Manage> PoolBuilder<M> {
+    pub(crate) fn new(manager: M) -> Self {
+        Self {
+            manager,
+            config: PoolConfig::default(),
+            hooks: Vec::new(),
+        }
+    }
+
+    /// Replaces all settings with the given `config`.
+    pub fn config(mut self, config: PoolConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Sets the maximum number of resources the pool can manage.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.config.max_size = max_size;
+        self
+    }
+
+    /// Sets the minimum number of idle resources the pool tries to keep.
+    pub fn min_idle(mut self, min_idle: usize) -> Self {
+        self.config.min_idle = min_idle;
+        self
+    }
+
+    /// Sets the maximum duration to wait for a resource in [`Pool::acquire`] and related methods.
+    pub fn wait_timeout(mut self, wait_timeout: Option<Duration>) -> Self {
+        self.config.wait_timeout = wait_timeout;
+        self
+    }
+
+    /// Sets the maximum duration a resource may stay idle in the pool.
+    pub fn idle_timeout(mut self, idle_timeout: Option<Duration>) -> Self {
+        self.config.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Sets the maximum lifetime of a resource.
+    pub fn max_lifetime(mut self, max_lifetime: Option<Duration>) -> Self {
+        self.config.max_lifetime = max_lifetime;
+        self
+    }
+
+    /// Sets whether resources are handed out strictly in the order they were requested.
+    ///
+    /// A fair pool never lets a new request overtake a task that is already waiting, which
+    /// bounds the wait time under heavy contention at the cost of some throughput.
+    pub fn fair(mut self, fair: bool) -> Self {
+        self.config.fair = fair;
+        self
+    }
+
+    /// Sets the order in which idle resources are handed out.
+    pub fn queue_strategy(mut self, queue_strategy: QueueStrategy) -> Self {
+        self.config.queue_strategy = queue_strategy;
+        self
+    }
+
+    /// Sets whether a returned resource is handed directly to a task waiting for one.
+    ///
+    /// A handed over resource goes to the task parked the longest rather than to whichever task
+    /// acquires the slot first. It does not save creations, since only tasks holding a slot take
+    /// idle resources either way, and it costs some throughput, so it is disabled by default.
+    pub fn handoff(mut self, handoff: bool) -> Self {
+        self.config.handoff = handoff;
+        self
+    }
+
+    /// Sets when resources are validated before they are acquired.
+    pub fn validation_policy(mut self, validation_policy: ValidationPolicy) -> Self {
+        self.config.validation_policy = validation_policy;
+        self
+    }
+
+    /// Sets whether newly created resources are validated before they are handed out or kept
+    /// idle.
+    pub fn validate_created(mut self, validate_created: bool) -> Self {
+        self.config.validate_created = validate_created;
+        self
+    }
+
+    /// Sets whether acquisitions fail with [`PoolError::Invalid`](crate::PoolError::Invalid)
+    /// instead of the creation error if every idle resource failed
+    /// [`Manage::check`](crate::resource::Manage::check) and a new one could not be created.
+    pub fn report_invalid(mut self, report_invalid: bool) -> Self {
+        self.config.report_invalid = report_invalid;
+        self
+    }
+
+    /// Sets how failed resource creations are retried while acquiring a resource.
+    pub fn create_retry(mut self, create_retry: RetryPolicy) -> Self {
+        self.config.create_retry = create_retry;
+        self
+    }
+
+    /// Sets the circuit breaker which stops resource creations after repeated failures.
+    pub fn circuit_breaker(mut self, circuit_breaker: Option<CircuitBreaker>) -> Self {
+        self.config.circuit_breaker = circuit_breaker;
+        self
+    }
+
+    /// Sets the maximum number of resources created at the same time.
+    pub fn max_concurrent_creates(mut self, max_concurrent_creates: Option<usize>) -> Self {
+        self.config.max_concurrent_creates = max_concurrent_creates;
+        self
+    }
+
+    /// Sets the maximum duration of each resource creation, recycling and validation.
+    pub fn create_timeout(mut self, create_timeout: Option<Duration>) -> Self {
+        self.config.create_timeout = create_timeout;
+        self
+    }
+
+    /// Registers a hook which is called on every [`Event`] of the pool.
+    pub fn hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Event<'_, M::Error>) + Send + Sync + 'static,
+    {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Builds the [`Pool`].
+    ///
+    /// If `min_idle` is nonzero, this must be called within a Tokio runtime because the pool
+    /// spawns a task to replenish idle resources.
+    ///
+    /// # Errors
+    ///
+    /// Fails with a [`ConfigError`] if `max_size` or `max_concurrent_creates` is zero, or if
+    /// `min_idle` is greater than `max_size`.
+    pub fn build(self) -> Result<Pool<M>, ConfigError> {
+        self.config.validate()?;
+        let min_idle = self.config.min_idle;
+        let pool = Pool::from_builder(self.manager, self.config, self.hooks);
+        if min_idle > 0 {
+            pool.set_min_idle(min_idle);
+        }
+        Ok(pool)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct Manager;
+
+    #[async_trait::async_trait]
+    impl Manage for Manager {
+        type Output = ();
+        type Error = ();
+
+        async fn try_create(&self) -> Result<Self::Output, Self::Error> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_build() {
+        let created = Arc::new(AtomicUsize::new(0));
+        let pool = Pool::builder(Manager)
+            .config(PoolConfig {
+                max_size: 2,
+                idle_timeout: Some(Duration::from_secs(60)),
+                fair: true,
+                queue_strategy: QueueStrategy::Lifo,
+                ..PoolConfig::default()
+            })
+            .hook({
+                let created = created.clone();
+                move |event| {
+                    if let Event::Created = event {
+                        created.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+            })
+            .build()
+            .unwrap();
+        assert_eq!(pool.max_size(), 2);
+        assert_eq!(pool.idle_timeout(), Some(Duration::from_secs(60)));
+
+        pool.reserve(2).await.unwrap();
+        assert_eq!(created.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_build_invalid() {
+        let build = |config| Pool::builder(Manager).config(config).build().err();
+        assert_eq!(
+            build(PoolConfig {
+                max_size: 0,
+                ..PoolConfig::default()
+            }),
+            Some(ConfigError::ZeroMaxSize)
+        );
+        assert_eq!(
+            build(PoolConfig {
+                max_size: 2,
+                min_idle: 3,
+                ..PoolConfig::default()
+            }),
+            Some(ConfigError::MinIdleAboveMaxSize)
+        );
+        assert_eq!(
+            build(PoolConfig {
+                max_concurrent_creates: Some(0),
+                ..PoolConfig::default()
+            }),
+            Some(ConfigError::ZeroMaxConcurrentCreates)
+        );
+        assert!(build(PoolConfig {
+            max_size: 2,
+            min_idle: 2,
+            ..PoolConfig::default()
+        })
+        .is_none());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserialize() {
+        let config: PoolConfig = toml::from_str(
+            r#"
+            max_size = 16
+            wait_timeout = "5s"
+            validation_policy = { IfIdleLongerThan = "1m 30s" }
+
+            [create_retry]
+            max_attempts = 3
+            initial_backoff = "250ms"
+
+            [circuit_breaker]
+            cool_down = "1m"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            PoolConfig {
+                max_size: 16,
+                wait_timeout: Some(Duration::from_secs(5)),
+                validation_policy: ValidationPolicy::IfIdleLongerThan(Duration::from_secs(90)),
+                create_retry: RetryPolicy {
+                    max_attempts: 3,
+                    initial_backoff: Duration::from_millis(250),
+                    ..RetryPolicy::default()
+                },
+                circuit_breaker: Some(CircuitBreaker {
+                    cool_down: Duration::from_secs(60),
+                    ..CircuitBreaker::default()
+                }),
+                ..PoolConfig::default()
+            }
+        );
+
+        let value = serde_json::to_value(&config).unwrap();
+        assert_eq!(value["wait_timeout"], "5s");
+        assert_eq!(serde_json::from_value::<PoolConfig>(value).unwrap(), config);
+    }
+}
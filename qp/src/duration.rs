@@ -0,0 +1,150 @@
+//! (De)serializes durations as human-readable strings such as `"5s"`, `"250ms"` or `"1m 30s"`.
+//!
+//! Use it with `#[serde(with = "crate::duration")]`, or with `crate::duration::option` for
+//! `Option<Duration>`.
+use serde::de::{self, Deserializer, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use std::time::Duration;
+
+/// The units from the largest to the smallest, with their length in nanoseconds.
+const UNITS: [(&str, u128); 6] = [
+    ("h", 3_600_000_000_000),
+    ("m", 60_000_000_000),
+    ("s", 1_000_000_000),
+    ("ms", 1_000_000),
+    ("us", 1_000),
+    ("ns", 1),
+];
+
+pub(crate) fn serialize<S: Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    Human(*duration).serialize(serializer)
+}
+
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Duration, D::Error> {
+    Human::deserialize(deserializer).map(|human| human.0)
+}
+
+pub(crate) mod option {
+    use super::Human;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub(crate) fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => serializer.serialize_some(&Human(*duration)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Option::<Human>::deserialize(deserializer).map(|human| human.map(|human| human.0))
+    }
+}
+
+struct Human(Duration);
+
+impl fmt::Display for Human {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let nanos = self.0.as_nanos();
+        if nanos == 0 {
+            return f.write_str("0s");
+        }
+        // Use the largest unit which represents the duration exactly.
+        let (unit, scale) = UNITS
+            .iter()
+            .find(|(_, scale)| nanos % scale == 0)
+            .unwrap_or(&UNITS[UNITS.len() - 1]);
+        write!(f, "{}{}", nanos / scale, unit)
+    }
+}
+
+impl Serialize for Human {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Human {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(HumanVisitor)
+    }
+}
+
+struct HumanVisitor;
+
+impl Visitor<'_> for HumanVisitor {
+    type Value = Human;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a duration such as \"5s\" or \"1m 30s\"")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        parse(value)
+            .map(Human)
+            .ok_or_else(|| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
+}
+
+/// Parses a sequence of numbers with units, such as `"1h 30m"`.
+fn parse(value: &str) -> Option<Duration> {
+    let mut rest = value.trim_start();
+    if rest.is_empty() {
+        return None;
+    }
+    let mut nanos: u128 = 0;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (number, tail) = rest.split_at(digits);
+        let letters = tail
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(letters);
+        let (_, scale) = UNITS.iter().find(|(name, _)| *name == unit)?;
+        nanos = number
+            .parse::<u128>()
+            .ok()?
+            .checked_mul(*scale)?
+            .checked_add(nanos)?;
+        rest = tail.trim_start();
+    }
+    let secs = u64::try_from(nanos / 1_000_000_000).ok()?;
+    Some(Duration::new(secs, (nanos % 1_000_000_000) as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duration() {
+        for (text, duration) in [
+            ("0s", Duration::ZERO),
+            ("250ms", Duration::from_millis(250)),
+            ("90s", Duration::from_secs(90)),
+            ("2h", Duration::from_secs(7200)),
+            ("1500us", Duration::from_micros(1500)),
+        ] {
+            assert_eq!(Human(duration).to_string(), text);
+            assert_eq!(parse(text), Some(duration));
+        }
+        assert_eq!(parse("1m 30s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse("1h30m"), Some(Duration::from_secs(5400)));
+        for text in ["", "5", "s", "5 sec", "-5s"] {
+            assert_eq!(parse(text), None);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::error::Error;
+use std::fmt;
+
+/// An error returned from [`Pool`](crate::Pool) when a resource cannot be acquired.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum PoolError<E> {
+    /// Timed out while waiting for a resource.
+    Timeout,
+    /// No resource is available without waiting.
+    WouldBlock,
+    /// The pool has been closed.
+    Closed,
+    /// The resource manager failed to create a resource.
+    Backend(E),
+    /// The resource failed validation.
+    Validation,
+    /// The resource failed [`Manage::check`](crate::resource::Manage::check) with the given error.
+    Invalid(E),
+    /// Resource creation is suspended by the circuit breaker after repeated failures.
+    Unavailable,
+    /// Creating a resource took longer than the create timeout, or was still running at the
+    /// deadline of the [`RetryPolicy`](crate::RetryPolicy) before any attempt failed.
+    ///
+    /// A resource whose recycling or validation times out is destroyed instead, and the pool
+    /// moves on to another one.
+    CreateTimeout,
+    /// The weight of the acquisition exceeds the maximum size of the pool, which was shrunk by
+    /// [`Pool::resize`](crate::Pool::resize) while it was waiting.
+    Oversized,
+}
+
+impl<E> From<E> for PoolError<E> {
+    fn from(err: E) -> Self {
+        Self::Backend(err)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for PoolError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timeout => write!(f, "timed out while waiting for a resource"),
+            Self::WouldBlock => write!(f, "no resource is available without waiting"),
+            Self::Closed => write!(f, "pool has been closed"),
+            Self::Backend(err) => write!(f, "failed to create a resource: {}", err),
+            Self::Validation => write!(f, "resource failed validation"),
+            Self::Invalid(err) => write!(f, "resource failed validation: {}", err),
+            Self::Unavailable => {
+                write!(f, "resource creation is suspended after repeated failures")
+            }
+            Self::CreateTimeout => write!(f, "timed out while creating a resource"),
+            Self::Oversized => write!(f, "acquisition weighs more than the size of the pool"),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for PoolError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Backend(err) | Self::Invalid(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// An error returned from [`PoolBuilder::build`](crate::PoolBuilder::build) when the
+/// [`PoolConfig`](crate::PoolConfig) is invalid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// `max_size` is zero, so no resource could ever be acquired.
+    ZeroMaxSize,
+    /// `min_idle` is greater than `max_size`.
+    MinIdleAboveMaxSize,
+    /// `max_concurrent_creates` is zero, so no resource could ever be created.
+    ZeroMaxConcurrentCreates,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroMaxSize => write!(f, "max_size must be at least 1"),
+            Self::MinIdleAboveMaxSize => write!(f, "min_idle must not exceed max_size"),
+            Self::ZeroMaxConcurrentCreates => {
+                write!(f, "max_concurrent_creates must be at least 1")
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {}
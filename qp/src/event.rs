@@ -0,0 +1,25 @@
+/// An event emitted by [`Pool`](crate::Pool) to the registered hooks.
+///
+/// Hooks are registered by [`PoolBuilder::hook`](crate::PoolBuilder::hook).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Event<'a, E> {
+    /// A new resource has been created.
+    Created,
+    /// The resource manager failed to create a resource.
+    CreateFailed(&'a E),
+    /// Creating a resource took longer than the create timeout.
+    CreateTimedOut,
+    /// A resource has been acquired from the pool.
+    Acquired,
+    /// A resource has been returned to the pool.
+    Released,
+    /// The resource manager failed to recycle a returned resource.
+    RecycleFailed(&'a E),
+    /// A resource failed [`Manage::check`](crate::resource::Manage::check).
+    CheckFailed(&'a E),
+    /// An expired or invalid resource has been discarded.
+    Discarded,
+}
+
+pub(crate) type Hook<E> = Box<dyn Fn(&Event<'_, E>) + Send + Sync>;
@@ -1,7 +1,28 @@
 //! High Performance Async Generic Pool
+//!
+//! The pool depends on Tokio. Timeouts, creation backoff and the circuit breaker use the Tokio
+//! timer, and `min_idle` and [`Pool::spawn_reaper`] spawn Tokio tasks, so a pool using any of them
+//! must run within a Tokio runtime with the timer enabled. Outside a runtime,
+//! [`Manage::destroy`](resource::Manage::destroy) is not called.
+mod breaker;
+mod builder;
+#[cfg(feature = "serde")]
+mod duration;
+mod error;
+mod event;
+mod loom;
 mod pool;
 pub mod resource;
+#[cfg(not(loom))]
+mod stack;
+mod status;
 pub mod sync;
 
 pub use async_trait::async_trait;
-pub use pool::{Pool, Pooled};
+pub use builder::{
+    CircuitBreaker, PoolBuilder, PoolConfig, QueueStrategy, RetryPolicy, ValidationPolicy,
+};
+pub use error::{ConfigError, PoolError};
+pub use event::Event;
+pub use pool::{Pool, Pooled, PooledOwned};
+pub use status::{BreakerState, PoolCounters, PoolStatus};
@@ -0,0 +1,84 @@
+//! Concurrency primitives which are swapped for their [`loom`](https://docs.rs/loom)
+//! counterparts when compiled with `--cfg loom`, so that the model tests in `tests/loom.rs` can
+//! explore every interleaving of them.
+pub(crate) use self::imp::*;
+
+#[cfg(not(loom))]
+mod imp {
+    pub(crate) use crate::stack::Stack;
+    pub(crate) use crossbeam_queue::SegQueue;
+    pub(crate) use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize};
+
+    /// [`std::cell::UnsafeCell`] with the closure-based API of `loom`.
+    pub(crate) struct UnsafeCell<T>(std::cell::UnsafeCell<T>);
+
+    impl<T> UnsafeCell<T> {
+        pub(crate) const fn new(data: T) -> Self {
+            Self(std::cell::UnsafeCell::new(data))
+        }
+
+        pub(crate) fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+            f(self.0.get())
+        }
+    }
+}
+
+#[cfg(loom)]
+mod imp {
+    use std::collections::VecDeque;
+
+    pub(crate) use ::loom::cell::UnsafeCell;
+    pub(crate) use ::loom::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize};
+
+    /// A model of [`crossbeam_queue::SegQueue`] whose operations are atomic steps for `loom`.
+    pub(crate) struct SegQueue<T>(::loom::sync::Mutex<VecDeque<T>>);
+
+    impl<T> SegQueue<T> {
+        pub(crate) fn new() -> Self {
+            Self(::loom::sync::Mutex::new(VecDeque::new()))
+        }
+
+        pub(crate) fn push(&self, value: T) {
+            self.0.lock().unwrap().push_back(value);
+        }
+
+        pub(crate) fn pop(&self) -> Option<T> {
+            self.0.lock().unwrap().pop_front()
+        }
+
+        pub(crate) fn len(&self) -> usize {
+            self.0.lock().unwrap().len()
+        }
+    }
+
+    /// A model of `crate::stack::Stack` whose operations are atomic steps for `loom`.
+    pub(crate) struct Stack<T>(::loom::sync::Mutex<Vec<T>>);
+
+    impl<T> Stack<T> {
+        pub(crate) fn new() -> Self {
+            Self(::loom::sync::Mutex::new(Vec::new()))
+        }
+
+        pub(crate) fn push(&self, value: T) {
+            self.0.lock().unwrap().push(value);
+        }
+
+        pub(crate) fn pop(&self) -> Option<T> {
+            self.0.lock().unwrap().pop()
+        }
+
+        pub(crate) fn remove_if(&self, f: impl FnMut(&T) -> bool) -> Vec<T> {
+            let mut stack = self.0.lock().unwrap();
+            let (mut removed, kept): (Vec<T>, Vec<T>) =
+                std::mem::take(&mut *stack).into_iter().partition(f);
+            *stack = kept;
+            // Removed from the top to the bottom, like the real stack.
+            removed.reverse();
+            removed
+        }
+
+        pub(crate) fn len(&self) -> usize {
+            self.0.lock().unwrap().len()
+        }
+    }
+}
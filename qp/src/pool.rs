@@ -1,8 +1,24 @@
-use crate::resource::Manage;
-use crate::sync::{Semaphore, SemaphorePermit};
-use crossbeam_queue::ArrayQueue;
+use crate::breaker::Breaker;
+use crate::builder::{PoolBuilder, PoolConfig, QueueStrategy, RetryPolicy, ValidationPolicy};
+use crate::error::PoolError;
+use crate::event::{Event, Hook};
+use crate::loom::{AtomicBool, AtomicU64, AtomicUsize, SegQueue, Stack};
+use crate::resource::{Manage, Metadata};
+use crate::status::{PoolCounters, PoolStatus, Stats};
+use crate::sync::{AcquireError, OwnedSemaphorePermit, Semaphore, SemaphorePermit};
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
 use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::sync::{oneshot, Notify};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
 
 /// An async resource pool.
 pub struct Pool<M: Manage> {
@@ -20,24 +36,154 @@ impl<M: Manage> Clone for Pool<M> {
 impl<M: Manage> Pool<M> {
     /// Creates a new `Pool` with the given size.
     pub fn new(manager: M, max_size: usize) -> Self {
-        debug_assert!(max_size >= 1);
+        let config = PoolConfig {
+            max_size,
+            ..PoolConfig::default()
+        };
+        Self::from_builder(manager, config, Vec::new())
+    }
+
+    /// Creates a [`PoolBuilder`] to configure a new `Pool`.
+    pub fn builder(manager: M) -> PoolBuilder<M> {
+        PoolBuilder::new(manager)
+    }
+
+    /// Creates a new `Pool` with the given size and the default wait timeout.
+    ///
+    /// [`Pool::acquire`] and related methods fail with [`PoolError::Timeout`] if a resource cannot
+    /// be acquired within `wait_timeout`.
+    pub fn with_wait_timeout(manager: M, max_size: usize, wait_timeout: Duration) -> Self {
+        let config = PoolConfig {
+            max_size,
+            wait_timeout: Some(wait_timeout),
+            ..PoolConfig::default()
+        };
+        Self::from_builder(manager, config, Vec::new())
+    }
+
+    pub(crate) fn from_builder(manager: M, config: PoolConfig, hooks: Vec<Hook<M::Error>>) -> Self {
+        debug_assert!(config.max_size >= 1);
+        debug_assert!(config.max_concurrent_creates.map_or(true, |n| n >= 1));
         Self {
             inner: Arc::new(Inner {
-                manager,
-                resources: ArrayQueue::new(max_size),
-                semaphore: Semaphore::new(max_size),
+                manager: Arc::new(manager),
+                resources: Idle::new(config.queue_strategy),
+                max_size: AtomicUsize::new(config.max_size),
+                semaphore: Arc::new(if config.fair {
+                    Semaphore::new_fair(config.max_size)
+                } else {
+                    Semaphore::new(config.max_size)
+                }),
+                wait_timeout: config.wait_timeout,
+                handoff: config.handoff,
+                validation_policy: config.validation_policy,
+                validate_created: config.validate_created,
+                report_invalid: config.report_invalid,
+                create_retry: config.create_retry,
+                breaker: Breaker::new(config.circuit_breaker),
+                creates: config.max_concurrent_creates.map(Semaphore::new),
+                create_timeout: config.create_timeout,
+                idle_timeout: AtomicDuration::new(config.idle_timeout),
+                max_lifetime: AtomicDuration::new(config.max_lifetime),
+                min_idle: AtomicUsize::new(0),
+                next_id: AtomicU64::new(0),
+                replenisher: AtomicBool::new(false),
+                drained: AtomicBool::new(false),
+                replenish: Arc::new(Notify::new()),
+                hooks,
+                returned: Notify::new(),
+                released: Notify::new(),
+                parked: SegQueue::new(),
+                parking: AtomicUsize::new(0),
+                stats: Stats::default(),
             }),
         }
     }
 
     /// Acquires a resource from the pool.
-    pub async fn acquire(&self) -> Result<Pooled<'_, M>, M::Error> {
-        self.inner.acquire().await
+    ///
+    /// Waits at most for the default wait timeout of the pool, if any.
+    pub async fn acquire(&self) -> Result<Pooled<'_, M>, PoolError<M::Error>> {
+        let inner = &self.inner;
+        inner.timeout(inner.wait_timeout, inner.acquire(1)).await
+    }
+
+    /// Acquires a resource from the pool, failing with [`PoolError::Timeout`] if it takes longer
+    /// than `timeout`.
+    ///
+    /// The timeout covers waiting for a free slot as well as validating and creating the resource.
+    pub async fn acquire_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<Pooled<'_, M>, PoolError<M::Error>> {
+        self.inner
+            .timeout(Some(timeout), self.inner.acquire(1))
+            .await
+    }
+
+    /// Acquires a resource which occupies `weight` slots of the pool.
+    ///
+    /// While the returned resource is in use, up to `max_size - weight` other resources can be
+    /// acquired from the pool, so heavy operations can be weighted to keep them from crowding out
+    /// regular ones.
+    ///
+    /// Fails with [`PoolError::Oversized`] if the pool is shrunk below `weight` by
+    /// [`Pool::resize`] while waiting.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `weight` is zero or greater than the maximum size of the pool.
+    pub async fn acquire_weighted(
+        &self,
+        weight: usize,
+    ) -> Result<Pooled<'_, M>, PoolError<M::Error>> {
+        assert!(
+            weight >= 1 && weight <= self.max_size(),
+            "weight must be between 1 and max_size"
+        );
+        let inner = &self.inner;
+        inner
+            .timeout(inner.wait_timeout, inner.acquire(weight))
+            .await
+    }
+
+    /// Acquires an owned resource from the pool.
+    ///
+    /// Unlike [`Pooled`], the returned [`PooledOwned`] does not borrow the pool, so it can be
+    /// moved into spawned tasks.
+    pub async fn acquire_owned(&self) -> Result<PooledOwned<M>, PoolError<M::Error>> {
+        let inner = &self.inner;
+        inner
+            .timeout(inner.wait_timeout, inner.clone().acquire_owned())
+            .await
+    }
+
+    /// Tries to acquire a resource from the pool without waiting for a free slot.
+    ///
+    /// Returns [`PoolError::WouldBlock`] immediately if all resources are occupied, or if a new
+    /// one is needed while `max_concurrent_creates` creations are running. Otherwise, an idle
+    /// resource is validated according to the validation policy and returned, or a new one is
+    /// created.
+    pub async fn try_acquire(&self) -> Result<Pooled<'_, M>, PoolError<M::Error>> {
+        self.inner.try_acquire().await
+    }
+
+    /// Tries to acquire an idle resource from the pool without waiting or creating a new one.
+    ///
+    /// Returns [`PoolError::WouldBlock`] immediately if there is no free slot or no valid idle
+    /// resource.
+    pub async fn try_acquire_idle_only(&self) -> Result<Pooled<'_, M>, PoolError<M::Error>> {
+        self.inner.try_acquire_idle_only().await
     }
 
     /// Acquires a resource from the pool without checking whether the resource is valid.
-    pub async fn acquire_unchecked(&self) -> Result<Pooled<'_, M>, M::Error> {
-        self.inner.acquire_unchecked().await
+    ///
+    /// Waits at most for the default wait timeout of the pool, if any.
+    pub async fn acquire_unchecked(&self) -> Result<Pooled<'_, M>, PoolError<M::Error>> {
+        let inner = &self.inner;
+        inner
+            .timeout(inner.wait_timeout, inner.acquire_unchecked())
+            .await
     }
 
     /// Returns the resource manager of the pool.
@@ -47,11 +193,29 @@ impl<M: Manage> Pool<M> {
 
     /// Returns the number of resources the pool can manage.
     pub fn max_size(&self) -> usize {
-        self.inner.resources.capacity()
+        self.inner.max_size.load(Ordering::Acquire)
+    }
+
+    /// Changes the maximum number of resources the pool can manage.
+    ///
+    /// Growing the pool lets waiting tasks acquire resources at once. Shrinking it destroys
+    /// surplus idle resources, while acquired resources stay valid and are destroyed when they
+    /// are returned, until the pool is within its new size. Pending weighted acquisitions which
+    /// no longer fit in the pool fail with [`PoolError::Oversized`].
+    pub fn resize(&self, max_size: usize) {
+        debug_assert!(max_size >= 1);
+        let inner = &self.inner;
+        let old = inner.max_size.swap(max_size, Ordering::AcqRel);
+        if max_size > old {
+            inner.semaphore.add_permits(max_size - old);
+        } else if max_size < old {
+            inner.semaphore.remove_permits(old - max_size);
+            inner.shrink();
+        }
     }
 
     /// Reserves the resources for at least `size` more resources to be acquired from the pool.
-    pub async fn reserve(&self, size: usize) -> Result<(), M::Error> {
+    pub async fn reserve(&self, size: usize) -> Result<(), PoolError<M::Error>> {
         debug_assert!(size >= 1);
         self.inner.reserve(size).await
     }
@@ -60,50 +224,860 @@ impl<M: Manage> Pool<M> {
     pub fn size(&self) -> usize {
         self.inner.semaphore.available_permits()
     }
+
+    /// Returns a snapshot of the current state of the pool.
+    pub fn status(&self) -> PoolStatus {
+        let stats = &self.inner.stats;
+        PoolStatus {
+            max_size: self.max_size(),
+            total_created: stats.total_created.load(Ordering::Relaxed),
+            idle: self.inner.resources.len(),
+            in_use: stats.in_use.load(Ordering::Relaxed),
+            waiters: stats.waiters.load(Ordering::Relaxed),
+            breaker: self.inner.breaker.state(),
+        }
+    }
+
+    /// Returns the cumulative counters of the pool.
+    pub fn counters(&self) -> PoolCounters {
+        self.inner.stats.counters()
+    }
+
+    /// Closes the pool.
+    ///
+    /// All pending and future acquisitions fail with [`PoolError::Closed`]. Acquired resources can
+    /// still be used and are kept idle when returned, until [`Pool::drain`] destroys them.
+    pub fn close(&self) {
+        self.inner.close();
+    }
+
+    /// Returns `true` if the pool has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    /// Closes the pool, waits for all acquired resources to be returned, and then destroys every
+    /// idle resource.
+    pub async fn drain(&self) {
+        self.inner.drain().await;
+    }
+
+    /// Returns the maximum duration a resource may stay idle in the pool.
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.inner.idle_timeout.get()
+    }
+
+    /// Sets the maximum duration a resource may stay idle in the pool.
+    ///
+    /// Resources idle for longer are discarded instead of being acquired.
+    pub fn set_idle_timeout(&self, idle_timeout: Option<Duration>) {
+        self.inner.idle_timeout.set(idle_timeout);
+    }
+
+    /// Returns the maximum lifetime of a resource.
+    pub fn max_lifetime(&self) -> Option<Duration> {
+        self.inner.max_lifetime.get()
+    }
+
+    /// Sets the maximum lifetime of a resource.
+    ///
+    /// Resources older than `max_lifetime` are discarded instead of being acquired or returned to
+    /// the pool.
+    pub fn set_max_lifetime(&self, max_lifetime: Option<Duration>) {
+        self.inner.max_lifetime.set(max_lifetime);
+    }
+
+    /// Returns the minimum number of idle resources the pool tries to keep.
+    pub fn min_idle(&self) -> usize {
+        self.inner.min_idle.load(Ordering::Relaxed)
+    }
+
+    /// Sets the minimum number of idle resources the pool tries to keep.
+    ///
+    /// Once set to a nonzero value, a background task creates new resources whenever the number
+    /// of idle resources drops below `min_idle`, as long as the pool has a free slot.
+    pub fn set_min_idle(&self, min_idle: usize) {
+        debug_assert!(min_idle <= self.max_size());
+        self.inner.min_idle.store(min_idle, Ordering::Relaxed);
+        if min_idle > 0 && !self.inner.replenisher.swap(true, Ordering::AcqRel) {
+            self.spawn_replenisher();
+        }
+        self.inner.maintain();
+    }
+
+    fn spawn_replenisher(&self) {
+        let inner = Arc::downgrade(&self.inner);
+        let replenish = self.inner.replenish.clone();
+        tokio::spawn(async move {
+            loop {
+                // Retry periodically in case the previous creation failed.
+                let _ = tokio::time::timeout(REPLENISH_INTERVAL, replenish.notified()).await;
+                match inner.upgrade() {
+                    Some(inner) => inner.replenish().await,
+                    None => return,
+                }
+            }
+        });
+    }
+
+    /// Discards the idle resources which exceeded the idle timeout or the maximum lifetime.
+    ///
+    /// Returns the number of discarded resources.
+    pub fn reap(&self) -> usize {
+        self.inner.reap()
+    }
+
+    /// Spawns a background task which calls [`Pool::reap`] every `period`.
+    ///
+    /// The task stops once the pool and all acquired resources are dropped, returning the total
+    /// number of discarded resources.
+    pub fn spawn_reaper(&self, period: Duration) -> JoinHandle<usize> {
+        let inner = Arc::downgrade(&self.inner);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            let mut reaped = 0;
+            loop {
+                interval.tick().await;
+                match inner.upgrade() {
+                    Some(inner) => reaped += inner.reap(),
+                    None => return reaped,
+                }
+            }
+        })
+    }
 }
 
+const REPLENISH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The number of senders of tasks which are no longer parked tolerated before they are dropped.
+const PURGE_THRESHOLD: usize = 32;
+
 struct Inner<M: Manage> {
-    manager: M,
-    resources: ArrayQueue<M::Output>,
-    semaphore: Semaphore,
+    manager: Arc<M>,
+    resources: Idle<Entry<M::Output>>,
+    max_size: AtomicUsize,
+    semaphore: Arc<Semaphore>,
+    wait_timeout: Option<Duration>,
+    handoff: bool,
+    validation_policy: ValidationPolicy,
+    validate_created: bool,
+    report_invalid: bool,
+    create_retry: RetryPolicy,
+    breaker: Breaker,
+    creates: Option<Semaphore>,
+    create_timeout: Option<Duration>,
+    idle_timeout: AtomicDuration,
+    max_lifetime: AtomicDuration,
+    min_idle: AtomicUsize,
+    next_id: AtomicU64,
+    replenisher: AtomicBool,
+    replenish: Arc<Notify>,
+    /// Whether [`Inner::drain`] has destroyed the idle resources.
+    drained: AtomicBool,
+    hooks: Vec<Hook<M::Error>>,
+    returned: Notify,
+    released: Notify,
+    parked: SegQueue<oneshot::Sender<Entry<M::Output>>>,
+    /// The number of tasks in [`Inner::park`].
+    parking: AtomicUsize,
+    stats: Stats,
+}
+
+impl<M: Manage> Drop for Inner<M> {
+    fn drop(&mut self) {
+        // Wake up the replenisher to let it stop.
+        self.replenish.notify_one();
+        while let Some(entry) = self.resources.pop() {
+            self.destroy(entry.resource);
+        }
+    }
 }
 
 impl<M: Manage> Inner<M> {
-    async fn acquire(&self) -> Result<Pooled<'_, M>, M::Error> {
-        let permit = self.semaphore.acquire().await;
-        while let Some(resource) = self.resources.pop() {
-            if self.manager.validate(&resource).await {
-                return Ok(self.make_pooled(resource, permit));
-            }
+    async fn acquire(&self, weight: usize) -> Result<Pooled<'_, M>, PoolError<M::Error>> {
+        let (permit, handed) = self.permit(weight).await?;
+        Ok(Pooled::new(self, self.get(handed, true).await?, permit))
+    }
+
+    async fn acquire_owned(self: Arc<Self>) -> Result<PooledOwned<M>, PoolError<M::Error>> {
+        let (permit, handed) = self.owned_permit().await?;
+        let entry = self.get(handed, true).await?;
+        Ok(PooledOwned::new(self, entry, permit))
+    }
+
+    async fn try_acquire(&self) -> Result<Pooled<'_, M>, PoolError<M::Error>> {
+        let permit = self.try_permit()?;
+        Ok(Pooled::new(self, self.get(None, false).await?, permit))
+    }
+
+    async fn try_acquire_idle_only(&self) -> Result<Pooled<'_, M>, PoolError<M::Error>> {
+        let permit = self.try_permit()?;
+        match self.pop_valid().await {
+            Ok(entry) => Ok(Pooled::new(self, entry, permit)),
+            Err(_) => Err(PoolError::WouldBlock),
         }
-        Ok(self.make_pooled(self.manager.try_create().await?, permit))
     }
 
-    async fn acquire_unchecked(&self) -> Result<Pooled<'_, M>, M::Error> {
-        let permit = self.semaphore.acquire().await;
-        Ok(self.make_pooled(
-            match self.resources.pop() {
-                Some(resource) => resource,
-                None => self.manager.try_create().await?,
+    async fn acquire_unchecked(&self) -> Result<Pooled<'_, M>, PoolError<M::Error>> {
+        let (permit, handed) = self.permit(1).await?;
+        let handed = match handed {
+            Some(entry) => self.recycle(entry).await,
+            None => None,
+        };
+        let entry = match handed {
+            Some(entry) => Some(entry),
+            None => self.pop_recycled().await,
+        };
+        let entry = match entry {
+            Some(entry) => entry,
+            None => {
+                let _slot = self.create_slot().await?;
+                self.entry(self.create_retrying().await?)
+            }
+        };
+        Ok(Pooled::new(self, self.unless_closed(entry)?, permit))
+    }
+
+    async fn timeout<T>(
+        &self,
+        duration: Option<Duration>,
+        future: impl Future<Output = Result<T, PoolError<M::Error>>>,
+    ) -> Result<T, PoolError<M::Error>> {
+        match duration {
+            Some(duration) => match tokio::time::timeout(duration, future).await {
+                Ok(result) => result,
+                Err(_) => {
+                    Stats::incr(&self.stats.timeouts);
+                    Err(PoolError::Timeout)
+                }
             },
-            permit,
-        ))
+            None => future.await,
+        }
+    }
+
+    /// Acquires a permit, along with the resource handed over with it, if any.
+    async fn permit(
+        &self,
+        weight: usize,
+    ) -> Result<(SemaphorePermit<'_>, Option<Entry<M::Output>>), PoolError<M::Error>> {
+        if weight > 1 {
+            return match self.wait(self.semaphore.acquire_many(weight)).await {
+                Ok(permit) => Ok((permit, None)),
+                Err(AcquireError::TooLarge) => Err(PoolError::Oversized),
+                Err(_) => Err(PoolError::Closed),
+            };
+        }
+        if let Some(permit) = self.semaphore.try_acquire() {
+            return Ok((permit, None));
+        }
+        match self.park(self.semaphore.acquire()).await? {
+            Ok(permit) => Ok((permit, None)),
+            Err(entry) => Ok((SemaphorePermit::new(&self.semaphore, 1), Some(entry))),
+        }
     }
 
-    fn make_pooled<'a>(
-        &'a self,
-        resource: M::Output,
-        permit: SemaphorePermit<'a>,
-    ) -> Pooled<'a, M> {
-        Pooled {
+    async fn owned_permit(
+        &self,
+    ) -> Result<(OwnedSemaphorePermit, Option<Entry<M::Output>>), PoolError<M::Error>> {
+        if let Some(permit) = self.semaphore.clone().try_acquire_owned() {
+            return Ok((permit, None));
+        }
+        match self.park(self.semaphore.clone().acquire_owned()).await? {
+            Ok(permit) => Ok((permit, None)),
+            Err(entry) => {
+                let permit = OwnedSemaphorePermit::new(self.semaphore.clone(), 1);
+                Ok((permit, Some(entry)))
+            }
+        }
+    }
+
+    /// Waits for `acquire` to acquire a permit, or for a returned resource to be handed over
+    /// together with its permit by [`Inner::hand_over`].
+    async fn park<P, F>(
+        &self,
+        acquire: F,
+    ) -> Result<Result<P, Entry<M::Output>>, PoolError<M::Error>>
+    where
+        F: Future<Output = Result<P, AcquireError>>,
+    {
+        if !self.handoff {
+            return match self.wait(acquire).await {
+                Ok(permit) => Ok(Ok(permit)),
+                Err(_) => Err(PoolError::Closed),
+            };
+        }
+        tokio::pin!(acquire);
+        let (sender, receiver) = oneshot::channel();
+        self.parking.fetch_add(1, Ordering::SeqCst);
+        let mut parked = Parked {
             pool: self,
-            resource: Some(resource),
-            _permit: permit,
+            receiver,
+        };
+        // The senders of the tasks which acquired a permit on their own are only popped by
+        // handoffs, so drop them once they outnumber the parked tasks.
+        if self.parked.len() > 2 * self.parking.load(Ordering::SeqCst) + PURGE_THRESHOLD {
+            self.purge_parked();
+        }
+        self.parked.push(sender);
+        match self.wait(Race(acquire, &mut parked.receiver)).await {
+            Ok(Ok(permit)) => Ok(Ok(permit)),
+            Err(Ok(entry)) => Ok(Err(entry)),
+            // The senders are only dropped with the pool.
+            Ok(Err(_)) | Err(Err(_)) => Err(PoolError::Closed),
+        }
+    }
+
+    async fn wait<F: Future>(&self, future: F) -> F::Output {
+        let start = Instant::now();
+        let _waiting = self.stats.wait();
+        let output = future.await;
+        self.stats.add_wait_time(start.elapsed());
+        output
+    }
+
+    fn try_permit(&self) -> Result<SemaphorePermit<'_>, PoolError<M::Error>> {
+        if self.is_closed() {
+            return Err(PoolError::Closed);
+        }
+        self.semaphore.try_acquire().ok_or(PoolError::WouldBlock)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.semaphore.is_closed()
+    }
+
+    fn close(&self) {
+        self.semaphore.close();
+        if let Some(creates) = &self.creates {
+            creates.close();
+        }
+    }
+
+    async fn drain(&self) {
+        self.close();
+        loop {
+            let returned = self.returned.notified();
+            if self.stats.in_use.load(Ordering::Acquire) == 0 {
+                break;
+            }
+            returned.await;
+        }
+        self.drained.store(true, Ordering::SeqCst);
+        while let Some(entry) = self.resources.pop() {
+            self.discard(Event::Discarded);
+            self.manager.destroy(entry.resource).await;
+        }
+    }
+
+    /// Destroys a resource prepared for an acquisition if the pool was closed meanwhile.
+    fn unless_closed(
+        &self,
+        entry: Entry<M::Output>,
+    ) -> Result<Entry<M::Output>, PoolError<M::Error>> {
+        if self.is_closed() {
+            self.destroy(entry.resource);
+            return Err(PoolError::Closed);
+        }
+        Ok(entry)
+    }
+
+    /// Returns a valid idle resource or creates a new one, unless the pool has been closed. The
+    /// caller must hold a permit.
+    ///
+    /// Without `wait`, fails with [`PoolError::WouldBlock`] instead of waiting for a creation
+    /// slot.
+    async fn get(
+        &self,
+        handed: Option<Entry<M::Output>>,
+        wait: bool,
+    ) -> Result<Entry<M::Output>, PoolError<M::Error>> {
+        let entry = self.get_or_create(handed, wait).await?;
+        self.unless_closed(entry)
+    }
+
+    async fn get_or_create(
+        &self,
+        handed: Option<Entry<M::Output>>,
+        wait: bool,
+    ) -> Result<Entry<M::Output>, PoolError<M::Error>> {
+        let mut invalid = None;
+        if let Some(entry) = handed {
+            match self.prepare(entry).await {
+                Ok(entry) => return Ok(entry),
+                Err(err) => invalid = err,
+            }
+        }
+        let (invalid, _slot) = loop {
+            // Subscribe before looking for an idle resource not to miss one released meanwhile.
+            let released = self.released.notified();
+            tokio::pin!(released);
+            invalid = match self.pop_valid().await {
+                Ok(entry) => return Ok(entry),
+                Err(err) => err.or(invalid),
+            };
+            if !wait {
+                break (invalid, self.try_create_slot()?);
+            }
+            let slot = self.create_slot();
+            tokio::pin!(slot);
+            if let Ok(slot) = Race(slot, released).await {
+                break (invalid, slot?);
+            }
+        };
+        let entry = match self.create_retrying().await {
+            Ok(resource) => self.entry(resource),
+            Err(err) => {
+                return Err(match (invalid, err) {
+                    (Some(invalid), PoolError::Backend(_) | PoolError::CreateTimeout)
+                        if self.report_invalid =>
+                    {
+                        PoolError::Invalid(invalid)
+                    }
+                    (_, err) => err,
+                })
+            }
+        };
+        match self.check_created(&entry).await {
+            Ok(()) => Ok(entry),
+            Err(invalid) => {
+                self.destroy(entry.resource);
+                Err(invalid.map_or(PoolError::Validation, PoolError::Invalid))
+            }
+        }
+    }
+
+    /// Waits until the pool may start another creation, holding the returned slot until then.
+    async fn create_slot(&self) -> Result<Option<SemaphorePermit<'_>>, PoolError<M::Error>> {
+        match &self.creates {
+            Some(creates) => match creates.acquire().await {
+                Ok(slot) => Ok(Some(slot)),
+                Err(_) => Err(PoolError::Closed),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Takes a slot to start another creation if one is free.
+    fn try_create_slot(&self) -> Result<Option<SemaphorePermit<'_>>, PoolError<M::Error>> {
+        match &self.creates {
+            Some(_) if self.is_closed() => Err(PoolError::Closed),
+            Some(creates) => creates.try_acquire().map(Some).ok_or(PoolError::WouldBlock),
+            None => Ok(None),
+        }
+    }
+
+    /// Creates a resource, giving up at `deadline` as if the create timeout had elapsed.
+    async fn create(&self, deadline: Option<Instant>) -> Result<M::Output, PoolError<M::Error>> {
+        let attempt = self.breaker.admit().ok_or(PoolError::Unavailable)?;
+        let create = self.bounded(self.manager.try_create());
+        let created = match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline, create)
+                .await
+                .unwrap_or(None),
+            None => create.await,
+        };
+        match created {
+            Some(Ok(resource)) => {
+                attempt.succeeded();
+                Stats::incr(&self.stats.creations);
+                self.stats.total_created.fetch_add(1, Ordering::Relaxed);
+                self.emit(Event::Created);
+                Ok(resource)
+            }
+            Some(Err(err)) => {
+                attempt.failed();
+                Stats::incr(&self.stats.creation_failures);
+                self.emit(Event::CreateFailed(&err));
+                Err(PoolError::Backend(err))
+            }
+            None => {
+                attempt.failed();
+                Stats::incr(&self.stats.creation_failures);
+                self.emit(Event::CreateTimedOut);
+                Err(PoolError::CreateTimeout)
+            }
+        }
+    }
+
+    /// Runs a call into the manager, returning `None` if it takes longer than the create timeout.
+    async fn bounded<F: Future>(&self, future: F) -> Option<F::Output> {
+        match self.create_timeout {
+            Some(duration) => tokio::time::timeout(duration, future).await.ok(),
+            None => Some(future.await),
+        }
+    }
+
+    /// Creates a resource, retrying according to the retry policy.
+    async fn create_retrying(&self) -> Result<M::Output, PoolError<M::Error>> {
+        let policy = &self.create_retry;
+        let deadline = policy.deadline.map(|deadline| Instant::now() + deadline);
+        let mut backoff = policy.initial_backoff;
+        let mut attempts = 1;
+        let mut last = None;
+        loop {
+            let err = match self.create(deadline).await {
+                Ok(resource) => return Ok(resource),
+                Err(err @ (PoolError::Backend(_) | PoolError::CreateTimeout)) => err,
+                Err(err) => return Err(err),
+            };
+            if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                // An attempt cut short by the deadline reports the error of the previous one.
+                return Err(match (err, last) {
+                    (PoolError::CreateTimeout, Some(last)) => last,
+                    (err, _) => err,
+                });
+            }
+            if attempts >= policy.max_attempts {
+                return Err(err);
+            }
+            let delay = jitter(backoff);
+            if let Some(deadline) = deadline {
+                if Instant::now() + delay > deadline {
+                    return Err(err);
+                }
+            }
+            last = Some(err);
+            tokio::time::sleep(delay).await;
+            backoff = backoff.saturating_mul(2).min(policy.max_backoff);
+            attempts += 1;
+        }
+    }
+
+    /// Validates the resource if the validation policy requires it.
+    async fn check(&self, entry: &Entry<M::Output>) -> Result<(), Option<M::Error>> {
+        let metadata = &entry.metadata;
+        let required = match self.validation_policy {
+            ValidationPolicy::Always => true,
+            ValidationPolicy::Never => false,
+            ValidationPolicy::IfIdleLongerThan(duration) => {
+                Instant::now().saturating_duration_since(metadata.last_used_at) > duration
+            }
+            ValidationPolicy::EveryNthUse(n) => {
+                metadata.checkouts > 0 && metadata.checkouts.checked_rem(n) == Some(0)
+            }
+        };
+        if required {
+            self.validate(&entry.resource).await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Validates a newly created resource if `validate_created` is set.
+    async fn check_created(&self, entry: &Entry<M::Output>) -> Result<(), Option<M::Error>> {
+        if self.validate_created {
+            self.validate(&entry.resource).await
+        } else {
+            Ok(())
         }
     }
 
-    async fn reserve(&self, size: usize) -> Result<(), M::Error> {
-        debug_assert!(size <= self.resources.capacity());
+    /// Validates the resource, returning the error from [`Manage::check`] if it failed.
+    async fn validate(&self, resource: &M::Output) -> Result<(), Option<M::Error>> {
+        let result = self
+            .bounded(self.verify(resource))
+            .await
+            .unwrap_or(Err(None));
+        if let Err(invalid) = &result {
+            Stats::incr(&self.stats.validation_failures);
+            if let Some(err) = invalid {
+                self.emit(Event::CheckFailed(err));
+            }
+        }
+        result
+    }
+
+    async fn verify(&self, resource: &M::Output) -> Result<(), Option<M::Error>> {
+        self.manager.check(resource).await?;
+        if self.manager.validate(resource).await {
+            Ok(())
+        } else {
+            Err(None)
+        }
+    }
+
+    /// Returns a valid idle resource, or the last error from [`Manage::check`] if there is none.
+    async fn pop_valid(&self) -> Result<Entry<M::Output>, Option<M::Error>> {
+        let mut invalid = None;
+        while let Some(entry) = self.pop_alive() {
+            match self.prepare(entry).await {
+                Ok(entry) => return Ok(entry),
+                Err(err) => invalid = err.or(invalid),
+            }
+        }
+        Err(invalid)
+    }
+
+    async fn pop_recycled(&self) -> Option<Entry<M::Output>> {
+        while let Some(entry) = self.pop_alive() {
+            if let Some(entry) = self.recycle(entry).await {
+                return Some(entry);
+            }
+        }
+        None
+    }
+
+    /// Recycles and validates a returned resource, destroying it if either fails.
+    async fn prepare(&self, entry: Entry<M::Output>) -> Result<Entry<M::Output>, Option<M::Error>> {
+        let entry = self.recycle(entry).await.ok_or(None)?;
+        match self.check(&entry).await {
+            Ok(()) => Ok(entry),
+            Err(err) => {
+                self.destroy(entry.resource);
+                Err(err)
+            }
+        }
+    }
+
+    /// Recycles a returned resource, destroying it if it fails.
+    async fn recycle(&self, mut entry: Entry<M::Output>) -> Option<Entry<M::Output>> {
+        if !entry.dirty {
+            return Some(entry);
+        }
+        match self
+            .bounded(self.manager.recycle(&mut entry.resource))
+            .await
+        {
+            Some(Ok(())) => {
+                entry.dirty = false;
+                Some(entry)
+            }
+            Some(Err(err)) => {
+                self.emit(Event::RecycleFailed(&err));
+                self.destroy(entry.resource);
+                None
+            }
+            None => {
+                self.destroy(entry.resource);
+                None
+            }
+        }
+    }
+
+    fn pop_alive(&self) -> Option<Entry<M::Output>> {
+        let now = Instant::now();
+        let mut alive = None;
+        while let Some(entry) = self.resources.pop() {
+            if !self.is_expired(&entry, now) {
+                alive = Some(entry);
+                break;
+            }
+            self.destroy(entry.resource);
+        }
+        self.maintain();
+        alive
+    }
+
+    fn is_expired(&self, entry: &Entry<M::Output>, now: Instant) -> bool {
+        let expired = |since: Instant, timeout: Option<Duration>| {
+            timeout.map_or(false, |timeout| {
+                now.saturating_duration_since(since) >= timeout
+            })
+        };
+        expired(entry.metadata.created_at, self.max_lifetime.get())
+            || expired(entry.metadata.last_used_at, self.idle_timeout.get())
+    }
+
+    fn entry(&self, resource: M::Output) -> Entry<M::Output> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        Entry {
+            resource,
+            metadata: Metadata::new(id),
+            dirty: false,
+        }
+    }
+
+    fn check_out(&self, entry: &mut Entry<M::Output>) {
+        entry.metadata.last_used_at = Instant::now();
+        entry.metadata.checkouts += 1;
+        Stats::incr(&self.stats.acquires);
+        self.stats.in_use.fetch_add(1, Ordering::AcqRel);
+        self.emit(Event::Acquired);
+    }
+
+    /// Returns `true` if the resource has been handed over to a parked task together with its
+    /// permit, which must not be released then.
+    fn check_in(&self, entry: Option<Entry<M::Output>>, permit: usize) -> bool {
+        let handed = match entry {
+            Some(entry) => self.release(entry, permit == 1),
+            None => {
+                // The resource has been taken out of the pool.
+                self.stats.total_created.fetch_sub(1, Ordering::Relaxed);
+                self.maintain();
+                false
+            }
+        };
+        if self.stats.in_use.fetch_sub(1, Ordering::AcqRel) == 1 && self.is_closed() {
+            self.returned.notify_waiters();
+        }
+        handed
+    }
+
+    fn release(&self, mut entry: Entry<M::Output>, handoff: bool) -> bool {
+        let now = Instant::now();
+        entry.metadata.last_used_at = now;
+        entry.dirty = true;
+        let mut handed = false;
+        if self.is_expired(&entry, now) {
+            self.destroy(entry.resource);
+        } else {
+            if self.is_oversized() {
+                self.destroy(entry.resource);
+            } else {
+                match self.hand_over(entry, handoff) {
+                    Ok(()) => handed = true,
+                    Err(entry) => {
+                        self.resources.push(entry);
+                        if self.drained.load(Ordering::SeqCst) {
+                            // Nothing destroys the idle resources of a drained pool anymore.
+                            while let Some(entry) = self.resources.pop() {
+                                self.destroy(entry.resource);
+                            }
+                        }
+                        if self.creates.is_some() {
+                            // Wake up the tasks waiting to create a resource.
+                            self.released.notify_waiters();
+                        }
+                    }
+                }
+                self.emit(Event::Released);
+            }
+        }
+        self.maintain();
+        handed
+    }
+
+    /// Hands the resource over to the task parked the longest, so that the task does not find the
+    /// pool empty when it wakes up.
+    fn hand_over(
+        &self,
+        mut entry: Entry<M::Output>,
+        handoff: bool,
+    ) -> Result<(), Entry<M::Output>> {
+        if !handoff || !self.handoff || self.is_closed() {
+            return Err(entry);
+        }
+        // A fair semaphore grants the released permit to the oldest waiter, which may be waiting
+        // for several permits, so a parked task must not take it first.
+        if self.semaphore.is_fair() && self.semaphore.has_waiters() {
+            return Err(entry);
+        }
+        // The permit must pay off the slots removed by `Pool::resize` first.
+        if self.semaphore.has_debt() {
+            return Err(entry);
+        }
+        while let Some(parked) = self.parked.pop() {
+            match parked.send(entry) {
+                Ok(()) => return Ok(()),
+                // The task is no longer waiting.
+                Err(returned) => entry = returned,
+            }
+        }
+        Err(entry)
+    }
+
+    /// Drops the senders of the tasks which are no longer parked, keeping the others in order.
+    fn purge_parked(&self) {
+        for _ in 0..self.parked.len() {
+            match self.parked.pop() {
+                Some(parked) if !parked.is_closed() => self.parked.push(parked),
+                Some(_) => {}
+                None => break,
+            }
+        }
+    }
+
+    fn is_oversized(&self) -> bool {
+        self.stats.total_created.load(Ordering::Relaxed) > self.max_size.load(Ordering::Acquire)
+    }
+
+    /// Destroys idle resources until the pool is within its maximum size.
+    fn shrink(&self) {
+        while self.is_oversized() {
+            match self.resources.pop() {
+                Some(entry) => self.destroy(entry.resource),
+                None => break,
+            }
+        }
+    }
+
+    /// Discards the resource, letting the manager destroy it on a background task.
+    ///
+    /// The resource is just dropped if there is no Tokio runtime.
+    fn destroy(&self, resource: M::Output) {
+        self.discard(Event::Discarded);
+        if let Ok(handle) = Handle::try_current() {
+            let manager = self.manager.clone();
+            handle.spawn(async move { manager.destroy(resource).await });
+        }
+    }
+
+    fn discard(&self, event: Event<'_, M::Error>) {
+        self.stats.total_created.fetch_sub(1, Ordering::Relaxed);
+        self.emit(event);
+    }
+
+    fn emit(&self, event: Event<'_, M::Error>) {
+        for hook in &self.hooks {
+            hook(&event);
+        }
+    }
+
+    /// Wakes up the replenisher if there are fewer idle resources than `min_idle`.
+    fn maintain(&self) {
+        if self.resources.len() < self.min_idle.load(Ordering::Relaxed) {
+            self.replenish.notify_one();
+        }
+    }
+
+    async fn replenish(&self) {
+        while !self.is_closed() && self.needs_replenish() {
+            let mut permit = match self.semaphore.try_acquire() {
+                Some(permit) => permit,
+                None => break,
+            };
+            let _slot = match self.create_slot().await {
+                Ok(slot) => slot,
+                Err(_) => break,
+            };
+            let entry = match self.create(None).await {
+                Ok(resource) => self.entry(resource),
+                Err(_) => break,
+            };
+            if self.check_created(&entry).await.is_err() {
+                self.destroy(entry.resource);
+                break;
+            }
+            match self.hand_over(entry, true) {
+                Ok(()) => permit.disarm(),
+                Err(entry) => self.resources.push(entry),
+            }
+        }
+    }
+
+    /// Returns `true` if there are fewer idle resources than `min_idle` and the pool has room for
+    /// another resource.
+    fn needs_replenish(&self) -> bool {
+        let idle = self.resources.len();
+        // Idle resources hold no permit, so they count against the free permits.
+        idle < self.min_idle.load(Ordering::Relaxed) && idle < self.semaphore.available_permits()
+    }
+
+    fn reap(&self) -> usize {
+        let now = Instant::now();
+        let expired = self
+            .resources
+            .remove_if(|entry| self.is_expired(entry, now));
+        let reaped = expired.len();
+        for entry in expired {
+            self.destroy(entry.resource);
+        }
+        self.maintain();
+        reaped
+    }
+
+    async fn reserve(&self, size: usize) -> Result<(), PoolError<M::Error>> {
+        debug_assert!(size <= self.max_size.load(Ordering::Acquire));
         let mut resources = Vec::with_capacity(size);
         for _ in 0..size {
             resources.push(self.acquire_unchecked().await?);
@@ -112,55 +1086,266 @@ impl<M: Manage> Inner<M> {
     }
 }
 
+struct Entry<T> {
+    resource: T,
+    metadata: Metadata,
+    /// Whether the resource has been used since it was created or recycled.
+    dirty: bool,
+}
+
+/// The idle resources, handed out in the order of the [`QueueStrategy`].
+// The pool holds a single instance, so boxing the cache-padded queue would only add an indirection.
+#[allow(clippy::large_enum_variant)]
+enum Idle<T> {
+    Fifo(SegQueue<T>),
+    Lifo(Stack<T>),
+}
+
+impl<T> Idle<T> {
+    fn new(strategy: QueueStrategy) -> Self {
+        match strategy {
+            QueueStrategy::Fifo => Self::Fifo(SegQueue::new()),
+            QueueStrategy::Lifo => Self::Lifo(Stack::new()),
+        }
+    }
+
+    fn push(&self, entry: T) {
+        match self {
+            Self::Fifo(queue) => queue.push(entry),
+            Self::Lifo(stack) => stack.push(entry),
+        }
+    }
+
+    fn pop(&self) -> Option<T> {
+        match self {
+            Self::Fifo(queue) => queue.pop(),
+            Self::Lifo(stack) => stack.pop(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Fifo(queue) => queue.len(),
+            Self::Lifo(stack) => stack.len(),
+        }
+    }
+
+    /// Removes the entries for which `f` returns `true`, keeping the others in their order.
+    fn remove_if(&self, mut f: impl FnMut(&T) -> bool) -> Vec<T> {
+        match self {
+            Self::Fifo(queue) => {
+                let mut removed = Vec::new();
+                for _ in 0..queue.len() {
+                    match queue.pop() {
+                        Some(entry) if f(&entry) => removed.push(entry),
+                        Some(entry) => queue.push(entry),
+                        None => break,
+                    }
+                }
+                removed
+            }
+            // Popping the entries would leave the stack empty for the concurrent acquisitions.
+            Self::Lifo(stack) => stack.remove_if(f),
+        }
+    }
+}
+
+/// A task waiting in [`Inner::park`].
+struct Parked<'a, M: Manage> {
+    pool: &'a Inner<M>,
+    receiver: oneshot::Receiver<Entry<M::Output>>,
+}
+
+impl<M: Manage> Drop for Parked<'_, M> {
+    fn drop(&mut self) {
+        self.receiver.close();
+        if let Ok(entry) = self.receiver.try_recv() {
+            // The resource was handed over after the task stopped waiting, so return it along
+            // with its permit.
+            let pool = self.pool;
+            if !pool.release(entry, true) {
+                drop(SemaphorePermit::new(&pool.semaphore, 1));
+            }
+        }
+        self.pool.parking.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Completes with the output of whichever future completes first, preferring the first one.
+struct Race<A, B>(A, B);
+
+impl<A: Future + Unpin, B: Future + Unpin> Future for Race<A, B> {
+    type Output = Result<A::Output, B::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Poll::Ready(output) = Pin::new(&mut self.0).poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        Pin::new(&mut self.1).poll(cx).map(Err)
+    }
+}
+
+/// Returns a random duration between half of `duration` and `duration`.
+fn jitter(duration: Duration) -> Duration {
+    let random = RandomState::new().build_hasher().finish();
+    let half = duration / 2;
+    half + half.mul_f64(random as f64 / u64::MAX as f64)
+}
+
+/// An optional [`Duration`] which can be updated while the pool is shared.
+struct AtomicDuration(AtomicU64);
+
+impl AtomicDuration {
+    const NONE: u64 = u64::MAX;
+
+    fn new(duration: Option<Duration>) -> Self {
+        let this = Self(AtomicU64::new(Self::NONE));
+        this.set(duration);
+        this
+    }
+
+    fn get(&self) -> Option<Duration> {
+        match self.0.load(Ordering::Relaxed) {
+            Self::NONE => None,
+            nanos => Some(Duration::from_nanos(nanos)),
+        }
+    }
+
+    fn set(&self, duration: Option<Duration>) {
+        let nanos = duration.map_or(Self::NONE, |duration| {
+            u64::try_from(duration.as_nanos()).unwrap_or(Self::NONE - 1)
+        });
+        self.0.store(nanos, Ordering::Relaxed);
+    }
+}
+
 /// An acquired resource from the pool.
 ///
 /// This type is created by the [`Pool::acquire`] method and related methods.
 pub struct Pooled<'a, M: Manage> {
     pool: &'a Inner<M>,
-    resource: Option<M::Output>,
-    _permit: SemaphorePermit<'a>,
+    entry: Option<Entry<M::Output>>,
+    permit: SemaphorePermit<'a>,
 }
 
 impl<M: Manage> Deref for Pooled<'_, M> {
     type Target = M::Output;
 
     fn deref(&self) -> &Self::Target {
-        self.resource.as_ref().unwrap()
+        &self.entry.as_ref().unwrap().resource
+    }
+}
+
+impl<M: Manage> DerefMut for Pooled<'_, M> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.entry.as_mut().unwrap().resource
+    }
+}
+
+impl<M: Manage> Drop for Pooled<'_, M> {
+    fn drop(&mut self) {
+        let permit = self.permit.num_permits();
+        if self.pool.check_in(self.entry.take(), permit) {
+            self.permit.disarm();
+        }
+    }
+}
+
+impl<'a, M: Manage> Pooled<'a, M> {
+    fn new(pool: &'a Inner<M>, mut entry: Entry<M::Output>, permit: SemaphorePermit<'a>) -> Self {
+        pool.check_out(&mut entry);
+        Self {
+            pool,
+            entry: Some(entry),
+            permit,
+        }
+    }
+
+    /// Returns `true` if the given resource is valid.
+    pub async fn is_valid(pooled: &Self) -> bool {
+        pooled.pool.verify(pooled).await.is_ok()
+    }
+
+    /// Returns the metadata of the given resource.
+    pub fn metadata(pooled: &Self) -> &Metadata {
+        &pooled.entry.as_ref().unwrap().metadata
+    }
+
+    /// Takes the raw resource out of the [`Pooled`], leaving a `None` in its place.
+    ///
+    /// This function consumes `pooled` to prevent double [`take`](Pooled::take).
+    pub fn take(mut pooled: Self) -> M::Output {
+        pooled.entry.take().unwrap().resource
+    }
+}
+
+/// An owned resource acquired from the pool.
+///
+/// This type is created by the [`Pool::acquire_owned`] method. Unlike [`Pooled`], it keeps the
+/// pool alive by itself and can be sent to other tasks.
+pub struct PooledOwned<M: Manage> {
+    pool: Arc<Inner<M>>,
+    entry: Option<Entry<M::Output>>,
+    permit: OwnedSemaphorePermit,
+}
+
+impl<M: Manage> Deref for PooledOwned<M> {
+    type Target = M::Output;
+
+    fn deref(&self) -> &Self::Target {
+        &self.entry.as_ref().unwrap().resource
     }
 }
 
-impl<M: Manage> DerefMut for Pooled<'_, M> {
+impl<M: Manage> DerefMut for PooledOwned<M> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        self.resource.as_mut().unwrap()
+        &mut self.entry.as_mut().unwrap().resource
     }
 }
 
-impl<M: Manage> Drop for Pooled<'_, M> {
+impl<M: Manage> Drop for PooledOwned<M> {
     fn drop(&mut self) {
-        if let Some(resource) = self.resource.take() {
-            let _ = self.pool.resources.push(resource);
+        let permit = self.permit.num_permits();
+        if self.pool.check_in(self.entry.take(), permit) {
+            self.permit.disarm();
         }
     }
 }
 
-impl<M: Manage> Pooled<'_, M> {
+impl<M: Manage> PooledOwned<M> {
+    fn new(pool: Arc<Inner<M>>, mut entry: Entry<M::Output>, permit: OwnedSemaphorePermit) -> Self {
+        pool.check_out(&mut entry);
+        Self {
+            pool,
+            entry: Some(entry),
+            permit,
+        }
+    }
+
     /// Returns `true` if the given resource is valid.
     pub async fn is_valid(pooled: &Self) -> bool {
-        pooled.pool.manager.validate(pooled).await
+        pooled.pool.verify(pooled).await.is_ok()
     }
 
-    /// Takes the raw resource out of the [`Pooled`], leaving a `None` in its place.
+    /// Returns the metadata of the given resource.
+    pub fn metadata(pooled: &Self) -> &Metadata {
+        &pooled.entry.as_ref().unwrap().metadata
+    }
+
+    /// Takes the raw resource out of the [`PooledOwned`], leaving a `None` in its place.
     ///
-    /// This function consumes `pooled` to prevent double [`take`](Pooled::take).
+    /// This function consumes `pooled` to prevent double [`take`](PooledOwned::take).
     pub fn take(mut pooled: Self) -> M::Output {
-        pooled.resource.take().unwrap()
+        pooled.entry.take().unwrap().resource
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::builder::CircuitBreaker;
+    use crate::status::BreakerState;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
     use std::time::Duration;
 
     #[derive(Default)]
@@ -180,6 +1365,18 @@ mod tests {
         }
     }
 
+    struct Counter(AtomicUsize);
+
+    #[async_trait::async_trait]
+    impl Manage for Counter {
+        type Output = usize;
+        type Error = ();
+
+        async fn try_create(&self) -> Result<Self::Output, Self::Error> {
+            Ok(self.0.fetch_add(1, Ordering::SeqCst))
+        }
+    }
+
     #[tokio::test]
     async fn test_abort_acquire() {
         let pool = Pool::new(Manager::default(), 1);
@@ -212,4 +1409,969 @@ mod tests {
         assert!(a.await.unwrap().is_err());
         assert!(b.await.unwrap().is_ok());
     }
+
+    #[tokio::test]
+    async fn test_acquire_timeout() {
+        let pool = Pool::with_wait_timeout(Manager::default(), 1, Duration::from_millis(1));
+
+        let obj = pool.acquire().await.unwrap();
+        assert!(matches!(pool.acquire().await, Err(PoolError::Timeout)));
+        assert!(matches!(
+            pool.acquire_timeout(Duration::from_millis(1)).await,
+            Err(PoolError::Timeout)
+        ));
+
+        drop(obj);
+        assert!(pool.acquire().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_try_acquire() {
+        let pool = Pool::new(Manager::default(), 1);
+
+        // No idle resource has been created yet.
+        assert!(matches!(
+            pool.try_acquire_idle_only().await,
+            Err(PoolError::WouldBlock)
+        ));
+
+        let obj = pool.try_acquire().await.unwrap();
+        assert!(matches!(
+            pool.try_acquire().await,
+            Err(PoolError::WouldBlock)
+        ));
+
+        drop(obj);
+        assert!(pool.try_acquire_idle_only().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_acquire_owned() {
+        let pool = Pool::new(Manager::default(), 1);
+
+        let obj = pool.acquire_owned().await.unwrap();
+        assert_eq!(pool.size(), 0);
+        tokio::spawn(async move {
+            assert!(PooledOwned::is_valid(&obj).await);
+        })
+        .await
+        .unwrap();
+
+        // The owned resource is returned to the pool when the task is done.
+        assert_eq!(pool.size(), 1);
+        assert!(pool.try_acquire_idle_only().await.is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reap() {
+        let pool = Pool::new(Manager::default(), 1);
+        pool.set_idle_timeout(Some(Duration::from_secs(10)));
+        pool.reserve(1).await.unwrap();
+
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert_eq!(pool.reap(), 0);
+
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert_eq!(pool.reap(), 1);
+        assert!(matches!(
+            pool.try_acquire_idle_only().await,
+            Err(PoolError::WouldBlock)
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_queue_strategy() {
+        let pool = Pool::builder(Counter(AtomicUsize::new(0)))
+            .max_size(3)
+            .idle_timeout(Some(Duration::from_secs(10)))
+            .queue_strategy(QueueStrategy::Lifo)
+            .build()
+            .unwrap();
+        let mut resources = Vec::new();
+        for _ in 0..3 {
+            resources.push(pool.acquire().await.unwrap());
+        }
+        drop(resources);
+
+        tokio::time::advance(Duration::from_secs(5)).await;
+        for _ in 0..3 {
+            // The most recently returned resource stays hot.
+            assert_eq!(*pool.acquire().await.unwrap(), 2);
+        }
+
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert_eq!(pool.reap(), 2);
+        assert_eq!(pool.status().idle, 1);
+
+        // Reaping the cold resource at the bottom keeps the others in order.
+        let cold = pool.acquire().await.unwrap();
+        let a = pool.acquire().await.unwrap();
+        let b = pool.acquire().await.unwrap();
+        assert_eq!((*cold, *a, *b), (2, 3, 4));
+        drop(cold);
+        tokio::time::advance(Duration::from_secs(5)).await;
+        drop(a);
+        drop(b);
+        tokio::time::advance(Duration::from_secs(6)).await;
+        assert_eq!(pool.reap(), 1);
+        let b = pool.acquire().await.unwrap();
+        let a = pool.acquire().await.unwrap();
+        assert_eq!((*a, *b), (3, 4));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_metadata() {
+        let pool = Pool::new(Counter(AtomicUsize::new(0)), 1);
+        let resource = pool.acquire().await.unwrap();
+        let metadata = *Pooled::metadata(&resource);
+        assert_eq!(metadata.id, 0);
+        assert_eq!(metadata.checkouts, 1);
+        assert_eq!(metadata.last_used_at, metadata.created_at);
+        drop(resource);
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        let resource = pool.acquire().await.unwrap();
+        let metadata = *Pooled::metadata(&resource);
+        assert_eq!(metadata.id, 0);
+        assert_eq!(metadata.checkouts, 2);
+        assert_eq!(
+            metadata.last_used_at - metadata.created_at,
+            Duration::from_secs(1)
+        );
+        Pooled::take(resource);
+
+        let resource = pool.acquire_owned().await.unwrap();
+        assert_eq!(PooledOwned::metadata(&resource).id, 1);
+    }
+
+    #[tokio::test]
+    async fn test_recycle() {
+        struct Recycler;
+
+        #[async_trait::async_trait]
+        impl Manage for Recycler {
+            type Output = i32;
+            type Error = ();
+
+            async fn try_create(&self) -> Result<Self::Output, Self::Error> {
+                Ok(0)
+            }
+
+            async fn recycle(&self, resource: &mut Self::Output) -> Result<(), Self::Error> {
+                if *resource < 0 {
+                    return Err(());
+                }
+                *resource = 0;
+                Ok(())
+            }
+        }
+
+        let pool = Pool::new(Recycler, 1);
+
+        *pool.acquire().await.unwrap() = 1;
+        let mut int = pool.acquire().await.unwrap();
+        assert_eq!(*int, 0);
+
+        // A resource failing to be recycled is discarded.
+        *int = -1;
+        drop(int);
+        assert!(matches!(
+            pool.try_acquire_idle_only().await,
+            Err(PoolError::WouldBlock)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_destroy() {
+        struct Destroyer(Arc<AtomicUsize>);
+
+        #[async_trait::async_trait]
+        impl Manage for Destroyer {
+            type Output = bool;
+            type Error = ();
+
+            async fn try_create(&self) -> Result<Self::Output, Self::Error> {
+                Ok(true)
+            }
+
+            async fn validate(&self, resource: &Self::Output) -> bool {
+                *resource
+            }
+
+            async fn destroy(&self, _resource: Self::Output) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let destroyed = Arc::new(AtomicUsize::new(0));
+        let pool = Pool::new(Destroyer(destroyed.clone()), 2);
+
+        // An invalid resource is destroyed on acquisition.
+        *pool.acquire().await.unwrap() = false;
+        let a = pool.acquire().await.unwrap();
+        let b = pool.acquire().await.unwrap();
+        tokio::task::yield_now().await;
+        assert_eq!(destroyed.load(Ordering::SeqCst), 1);
+
+        // Idle resources are destroyed with the pool.
+        drop((a, b));
+        drop(pool);
+        tokio::task::yield_now().await;
+        assert_eq!(destroyed.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_validation_policy() {
+        struct Validator;
+
+        #[async_trait::async_trait]
+        impl Manage for Validator {
+            type Output = bool;
+            type Error = ();
+
+            async fn try_create(&self) -> Result<Self::Output, Self::Error> {
+                Ok(true)
+            }
+
+            async fn validate(&self, resource: &Self::Output) -> bool {
+                *resource
+            }
+        }
+
+        let pool = Pool::builder(Validator)
+            .max_size(1)
+            .validation_policy(ValidationPolicy::EveryNthUse(2))
+            .build()
+            .unwrap();
+        *pool.acquire().await.unwrap() = false;
+        assert!(!*pool.acquire().await.unwrap());
+        assert!(*pool.acquire().await.unwrap());
+        assert_eq!(pool.counters().validation_failures, 1);
+
+        let pool = Pool::builder(Validator)
+            .max_size(1)
+            .validation_policy(ValidationPolicy::IfIdleLongerThan(Duration::from_secs(10)))
+            .build()
+            .unwrap();
+        *pool.acquire().await.unwrap() = false;
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert!(!*pool.acquire().await.unwrap());
+        tokio::time::advance(Duration::from_secs(11)).await;
+        assert!(*pool.acquire().await.unwrap());
+        assert_eq!(pool.counters().validation_failures, 1);
+    }
+
+    #[tokio::test]
+    async fn test_validate_created() {
+        struct Rejecter;
+
+        #[async_trait::async_trait]
+        impl Manage for Rejecter {
+            type Output = ();
+            type Error = ();
+
+            async fn try_create(&self) -> Result<Self::Output, Self::Error> {
+                Ok(())
+            }
+
+            async fn validate(&self, _resource: &Self::Output) -> bool {
+                false
+            }
+        }
+
+        let pool = Pool::new(Rejecter, 1);
+        assert!(pool.acquire().await.is_ok());
+        assert_eq!(pool.counters().validation_failures, 0);
+
+        let pool = Pool::builder(Rejecter)
+            .max_size(1)
+            .validate_created(true)
+            .build()
+            .unwrap();
+        assert!(matches!(pool.acquire().await, Err(PoolError::Validation)));
+        assert_eq!(pool.counters().validation_failures, 1);
+        assert_eq!(pool.size(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_check() {
+        struct Checker(AtomicBool);
+
+        #[async_trait::async_trait]
+        impl Manage for Checker {
+            type Output = bool;
+            type Error = &'static str;
+
+            async fn try_create(&self) -> Result<Self::Output, Self::Error> {
+                if self.0.load(Ordering::SeqCst) {
+                    Ok(true)
+                } else {
+                    Err("unreachable")
+                }
+            }
+
+            async fn check(&self, resource: &Self::Output) -> Result<(), Self::Error> {
+                if *resource {
+                    Ok(())
+                } else {
+                    Err("broken")
+                }
+            }
+        }
+
+        let failed = Arc::new(AtomicUsize::new(0));
+        let pool = Pool::builder(Checker(AtomicBool::new(true)))
+            .max_size(1)
+            .report_invalid(true)
+            .hook({
+                let failed = failed.clone();
+                move |event| {
+                    if let Event::CheckFailed(&"broken") = event {
+                        failed.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+            })
+            .build()
+            .unwrap();
+        let mut resource = pool.acquire().await.unwrap();
+        *resource = false;
+        assert!(!Pooled::is_valid(&resource).await);
+        drop(resource);
+
+        // The idle resource is broken and a new one cannot be created.
+        pool.manager().0.store(false, Ordering::SeqCst);
+        assert!(matches!(
+            pool.acquire().await,
+            Err(PoolError::Invalid("broken"))
+        ));
+        assert_eq!(failed.load(Ordering::SeqCst), 1);
+        assert_eq!(pool.counters().validation_failures, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_create_retry() {
+        struct Flaky(AtomicUsize);
+
+        #[async_trait::async_trait]
+        impl Manage for Flaky {
+            type Output = ();
+            type Error = usize;
+
+            async fn try_create(&self) -> Result<Self::Output, Self::Error> {
+                match self.0.fetch_add(1, Ordering::SeqCst) {
+                    n if n % 3 == 2 => Ok(()),
+                    n => Err(n),
+                }
+            }
+        }
+
+        let retry = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_secs(1),
+            ..RetryPolicy::default()
+        };
+        let pool = Pool::builder(Flaky(AtomicUsize::new(0)))
+            .max_size(1)
+            .create_retry(retry)
+            .build()
+            .unwrap();
+        let start = Instant::now();
+        Pooled::take(pool.acquire().await.unwrap());
+        assert!(start.elapsed() >= Duration::from_millis(1500));
+        assert!(start.elapsed() <= Duration::from_secs(3));
+        assert_eq!(pool.counters().creation_failures, 2);
+
+        let pool = Pool::builder(Flaky(AtomicUsize::new(0)))
+            .max_size(1)
+            .create_retry(RetryPolicy {
+                deadline: Some(Duration::from_millis(1500)),
+                ..retry
+            })
+            .build()
+            .unwrap();
+        // The last error is returned once the next attempt would start after the deadline.
+        assert!(matches!(pool.acquire().await, Err(PoolError::Backend(1))));
+
+        struct Hanging(AtomicUsize);
+
+        #[async_trait::async_trait]
+        impl Manage for Hanging {
+            type Output = ();
+            type Error = usize;
+
+            async fn try_create(&self) -> Result<Self::Output, Self::Error> {
+                match self.0.fetch_add(1, Ordering::SeqCst) {
+                    0 => Err(0),
+                    _ => std::future::pending().await,
+                }
+            }
+        }
+
+        // An attempt still running at the deadline is abandoned.
+        let pool = Pool::builder(Hanging(AtomicUsize::new(0)))
+            .max_size(1)
+            .create_retry(RetryPolicy {
+                deadline: Some(Duration::from_secs(5)),
+                ..retry
+            })
+            .build()
+            .unwrap();
+        let start = Instant::now();
+        assert!(matches!(pool.acquire().await, Err(PoolError::Backend(0))));
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+        assert_eq!(pool.counters().creation_failures, 2);
+
+        // The abandoned attempts count towards the circuit breaker.
+        let pool = Pool::builder(Hanging(AtomicUsize::new(1)))
+            .max_size(1)
+            .create_retry(RetryPolicy {
+                deadline: Some(Duration::from_secs(5)),
+                ..retry
+            })
+            .circuit_breaker(Some(CircuitBreaker {
+                failure_threshold: 2,
+                cool_down: Duration::from_secs(60),
+            }))
+            .build()
+            .unwrap();
+        for _ in 0..2 {
+            assert!(matches!(
+                pool.acquire().await,
+                Err(PoolError::CreateTimeout)
+            ));
+        }
+        assert_eq!(pool.counters().creation_failures, 2);
+        assert!(matches!(pool.acquire().await, Err(PoolError::Unavailable)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_circuit_breaker() {
+        struct Backend(AtomicBool);
+
+        #[async_trait::async_trait]
+        impl Manage for Backend {
+            type Output = ();
+            type Error = ();
+
+            async fn try_create(&self) -> Result<Self::Output, Self::Error> {
+                if self.0.load(Ordering::SeqCst) {
+                    Ok(())
+                } else {
+                    Err(())
+                }
+            }
+        }
+
+        let pool = Pool::builder(Backend(AtomicBool::new(false)))
+            .max_size(1)
+            .circuit_breaker(Some(CircuitBreaker {
+                failure_threshold: 2,
+                cool_down: Duration::from_secs(10),
+            }))
+            .build()
+            .unwrap();
+        for _ in 0..2 {
+            assert!(matches!(pool.acquire().await, Err(PoolError::Backend(()))));
+        }
+        assert!(matches!(pool.acquire().await, Err(PoolError::Unavailable)));
+        assert_eq!(pool.status().breaker, BreakerState::Open);
+        assert_eq!(pool.counters().creation_failures, 2);
+
+        pool.manager().0.store(true, Ordering::SeqCst);
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert!(pool.acquire().await.is_ok());
+        assert_eq!(pool.status().breaker, BreakerState::Closed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_max_concurrent_creates() {
+        #[derive(Default)]
+        struct Slow {
+            creating: AtomicUsize,
+            max_creating: AtomicUsize,
+        }
+
+        #[async_trait::async_trait]
+        impl Manage for Slow {
+            type Output = ();
+            type Error = ();
+
+            async fn try_create(&self) -> Result<Self::Output, Self::Error> {
+                let creating = self.creating.fetch_add(1, Ordering::SeqCst) + 1;
+                self.max_creating.fetch_max(creating, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                self.creating.fetch_sub(1, Ordering::SeqCst);
+                Ok(())
+            }
+        }
+
+        let pool = Pool::builder(Slow::default())
+            .max_size(8)
+            .max_concurrent_creates(Some(2))
+            .build()
+            .unwrap();
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let pool = pool.clone();
+                tokio::spawn(async move {
+                    let _resource = pool.acquire().await.unwrap();
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(pool.manager().max_creating.load(Ordering::SeqCst), 2);
+        // Waiters took the resources returned while they waited to create one.
+        assert!(pool.counters().creations < 8);
+
+        // `try_acquire` does not wait for a creation slot.
+        let pool = Pool::builder(Slow::default())
+            .max_size(2)
+            .max_concurrent_creates(Some(1))
+            .build()
+            .unwrap();
+        let creating = {
+            let pool = pool.clone();
+            tokio::spawn(async move { pool.acquire().await.map(drop) })
+        };
+        tokio::task::yield_now().await;
+        let start = Instant::now();
+        assert!(matches!(
+            pool.try_acquire().await,
+            Err(PoolError::WouldBlock)
+        ));
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        assert!(creating.await.unwrap().is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_create_timeout() {
+        struct Hanging(AtomicBool);
+
+        impl Hanging {
+            async fn hang(&self) {
+                if self.0.load(Ordering::SeqCst) {
+                    std::future::pending::<()>().await;
+                }
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl Manage for Hanging {
+            type Output = ();
+            type Error = ();
+
+            async fn try_create(&self) -> Result<Self::Output, Self::Error> {
+                self.hang().await;
+                Ok(())
+            }
+
+            async fn validate(&self, _resource: &Self::Output) -> bool {
+                self.hang().await;
+                true
+            }
+        }
+
+        let timed_out = Arc::new(AtomicUsize::new(0));
+        let pool = Pool::builder(Hanging(AtomicBool::new(true)))
+            .max_size(1)
+            .create_timeout(Some(Duration::from_secs(1)))
+            .hook({
+                let timed_out = timed_out.clone();
+                move |event| {
+                    if let Event::CreateTimedOut = event {
+                        timed_out.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+            })
+            .build()
+            .unwrap();
+        assert!(matches!(
+            pool.acquire().await,
+            Err(PoolError::CreateTimeout)
+        ));
+        assert_eq!(pool.size(), 1);
+        assert_eq!(pool.counters().creation_failures, 1);
+        assert_eq!(timed_out.load(Ordering::SeqCst), 1);
+
+        pool.manager().0.store(false, Ordering::SeqCst);
+        drop(pool.acquire().await.unwrap());
+        assert_eq!(pool.status().idle, 1);
+
+        // An idle resource whose validation hangs is destroyed.
+        pool.manager().0.store(true, Ordering::SeqCst);
+        assert!(matches!(
+            pool.acquire().await,
+            Err(PoolError::CreateTimeout)
+        ));
+        assert_eq!(pool.status().idle, 0);
+        assert_eq!(pool.counters().validation_failures, 1);
+        assert_eq!(pool.size(), 1);
+        // Only the creation which replaced it emits an event.
+        assert_eq!(timed_out.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_hand_over() {
+        let pool = Pool::builder(Counter(AtomicUsize::new(0)))
+            .max_size(1)
+            .handoff(true)
+            .build()
+            .unwrap();
+        let resource = pool.acquire().await.unwrap();
+        let waiter = tokio::spawn({
+            let pool = pool.clone();
+            async move { *pool.acquire().await.unwrap() }
+        });
+        tokio::task::yield_now().await;
+
+        // The returned resource goes straight to the waiting task.
+        drop(resource);
+        assert_eq!(pool.status().idle, 0);
+        assert_eq!(pool.size(), 0);
+        assert_eq!(waiter.await.unwrap(), 0);
+
+        // A resource handed over to a task which stopped waiting is returned to the pool.
+        let resource = pool.acquire().await.unwrap();
+        let mut acquire = Box::pin(pool.acquire());
+        tokio::select! {
+            biased;
+            _ = &mut acquire => unreachable!(),
+            _ = tokio::task::yield_now() => {}
+        }
+        drop(resource);
+        drop(acquire);
+        assert_eq!(pool.status().idle, 1);
+        assert_eq!(pool.size(), 1);
+        assert_eq!(pool.counters().creations, 1);
+
+        // The tasks which acquired a slot on their own do not leave their senders behind.
+        for _ in 0..1000 {
+            let resource = pool.acquire().await.unwrap();
+            let waiter = tokio::spawn({
+                let pool = pool.clone();
+                async move { Pooled::take(pool.acquire().await.unwrap()) }
+            });
+            tokio::task::yield_now().await;
+            Pooled::take(resource);
+            waiter.await.unwrap();
+        }
+        assert!(pool.inner.parked.len() <= PURGE_THRESHOLD + 3);
+
+        // By default, the returned resource goes back to the pool for the waiting task.
+        let pool = Pool::new(Counter(AtomicUsize::new(0)), 1);
+        let resource = pool.acquire().await.unwrap();
+        let waiter = tokio::spawn({
+            let pool = pool.clone();
+            async move { *pool.acquire().await.unwrap() }
+        });
+        tokio::task::yield_now().await;
+        drop(resource);
+        assert_eq!(pool.status().idle, 1);
+        assert_eq!(waiter.await.unwrap(), 0);
+        assert_eq!(pool.inner.parked.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_hand_over_fair() {
+        let pool = Pool::builder(Counter(AtomicUsize::new(0)))
+            .max_size(2)
+            .fair(true)
+            .handoff(true)
+            .build()
+            .unwrap();
+        let a = pool.acquire().await.unwrap();
+        let b = pool.acquire().await.unwrap();
+        let mut heavy = Box::pin(pool.acquire_weighted(2));
+        let mut light = Box::pin(pool.acquire());
+        tokio::select! {
+            biased;
+            _ = &mut heavy => unreachable!(),
+            _ = tokio::task::yield_now() => {}
+        }
+        tokio::select! {
+            biased;
+            _ = &mut light => unreachable!(),
+            _ = tokio::task::yield_now() => {}
+        }
+
+        // The returned resource is not handed to the light acquisition queued last.
+        drop(a);
+        tokio::select! {
+            biased;
+            _ = &mut light => unreachable!(),
+            _ = tokio::task::yield_now() => {}
+        }
+        drop(b);
+        let heavy = heavy.await.unwrap();
+        tokio::select! {
+            biased;
+            _ = &mut light => unreachable!(),
+            _ = tokio::task::yield_now() => {}
+        }
+        drop(heavy);
+        light.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_drain() {
+        let pool = Pool::new(Manager::default(), 1);
+        let obj = pool.acquire().await.unwrap();
+
+        let waiter = {
+            let pool = pool.clone();
+            tokio::spawn(async move { pool.acquire().await.map(|_| ()) })
+        };
+        let mut drain = {
+            let pool = pool.clone();
+            tokio::spawn(async move { pool.drain().await })
+        };
+        tokio::task::yield_now().await;
+
+        // The pending acquisition fails, while the drain waits for the acquired resource.
+        assert!(matches!(waiter.await.unwrap(), Err(PoolError::Closed)));
+        assert!(matches!(pool.try_acquire().await, Err(PoolError::Closed)));
+        assert!(tokio::time::timeout(Duration::from_millis(10), &mut drain)
+            .await
+            .is_err());
+
+        drop(obj);
+        drain.await.unwrap();
+        assert!(pool.is_closed());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_drain_creating() {
+        struct Slow(Arc<AtomicUsize>);
+
+        #[async_trait::async_trait]
+        impl Manage for Slow {
+            type Output = ();
+            type Error = ();
+
+            async fn try_create(&self) -> Result<Self::Output, Self::Error> {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                Ok(())
+            }
+
+            async fn destroy(&self, _resource: Self::Output) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let destroyed = Arc::new(AtomicUsize::new(0));
+        let pool = Pool::builder(Slow(destroyed.clone()))
+            .max_size(2)
+            .max_concurrent_creates(Some(1))
+            .build()
+            .unwrap();
+        let creating = {
+            let pool = pool.clone();
+            tokio::spawn(async move { pool.acquire().await.map(drop) })
+        };
+        let waiting = {
+            let pool = pool.clone();
+            tokio::spawn(async move { pool.acquire().await.map(drop) })
+        };
+        tokio::task::yield_now().await;
+
+        // The task waiting to start a creation fails at once, and the resource created for the
+        // other one is destroyed instead of being kept idle.
+        pool.drain().await;
+        assert!(matches!(waiting.await.unwrap(), Err(PoolError::Closed)));
+        assert!(matches!(creating.await.unwrap(), Err(PoolError::Closed)));
+        tokio::task::yield_now().await;
+        assert_eq!(pool.status().idle, 0);
+        assert_eq!(destroyed.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_acquire_weighted() {
+        let pool = Pool::new(Manager::default(), 3);
+
+        // A weighted resource occupies as many slots as its weight.
+        let heavy = pool.acquire_weighted(3).await.unwrap();
+        assert_eq!(pool.size(), 0);
+        assert!(matches!(
+            pool.try_acquire().await,
+            Err(PoolError::WouldBlock)
+        ));
+
+        drop(heavy);
+        assert_eq!(pool.size(), 3);
+        let _obj = pool.acquire().await.unwrap();
+        assert_eq!(pool.size(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_resize() {
+        let pool = Pool::new(Counter(AtomicUsize::new(0)), 2);
+        let a = pool.acquire().await.unwrap();
+        let b = pool.acquire().await.unwrap();
+
+        // Shrinking keeps acquired resources, destroying them on return until the pool fits.
+        pool.resize(1);
+        assert_eq!(pool.max_size(), 1);
+        drop(a);
+        assert_eq!(pool.size(), 0);
+        assert_eq!(pool.status().total_created, 1);
+        drop(b);
+        assert_eq!(pool.size(), 1);
+        assert_eq!(pool.status().idle, 1);
+
+        // Growing lets a waiting task acquire a resource at once.
+        let c = pool.acquire().await.unwrap();
+        let waiter = {
+            let pool = pool.clone();
+            tokio::spawn(async move { *pool.acquire().await.unwrap() })
+        };
+        tokio::task::yield_now().await;
+        pool.resize(2);
+        assert_eq!(waiter.await.unwrap(), 2);
+        assert_eq!(*c, 1);
+    }
+
+    #[tokio::test]
+    async fn test_resize_weighted() {
+        let pool = Pool::builder(Counter(AtomicUsize::new(0)))
+            .max_size(4)
+            .fair(true)
+            .build()
+            .unwrap();
+        let mut resources = Vec::new();
+        for _ in 0..4 {
+            resources.push(pool.acquire().await.unwrap());
+        }
+        let heavy = {
+            let pool = pool.clone();
+            tokio::spawn(async move { pool.acquire_weighted(4).await.map(drop) })
+        };
+        tokio::task::yield_now().await;
+
+        // A waiter which no longer fits in the shrunk pool fails instead of blocking the others.
+        pool.resize(2);
+        let heavy = tokio::time::timeout(Duration::from_secs(1), heavy).await;
+        assert!(matches!(heavy.unwrap().unwrap(), Err(PoolError::Oversized)));
+        drop(resources);
+        let acquire = tokio::time::timeout(Duration::from_secs(1), pool.acquire());
+        assert!(acquire.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_resize_after_hand_over() {
+        let pool = Pool::builder(Counter(AtomicUsize::new(0)))
+            .max_size(2)
+            .handoff(true)
+            .build()
+            .unwrap();
+        let a = pool.acquire().await.unwrap();
+        let b = pool.acquire().await.unwrap();
+        let mut acquire = Box::pin(pool.acquire());
+        tokio::select! {
+            biased;
+            _ = &mut acquire => unreachable!(),
+            _ = tokio::task::yield_now() => {}
+        }
+        // The slot handed over to a task which stopped waiting is returned, not added.
+        drop(a);
+        drop(acquire);
+        assert_eq!(pool.size(), 1);
+
+        let heavy = {
+            let pool = pool.clone();
+            tokio::spawn(async move { pool.acquire_weighted(2).await.map(drop) })
+        };
+        tokio::task::yield_now().await;
+        pool.resize(1);
+        let heavy = tokio::time::timeout(Duration::from_secs(1), heavy).await;
+        assert!(matches!(heavy.unwrap().unwrap(), Err(PoolError::Oversized)));
+        drop(b);
+        assert_eq!(pool.size(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_resize_hand_over() {
+        let pool = Pool::builder(Counter(AtomicUsize::new(0)))
+            .max_size(3)
+            .handoff(true)
+            .build()
+            .unwrap();
+        let heavy = pool.acquire_weighted(2).await.unwrap();
+        let single = pool.acquire().await.unwrap();
+        let mut waiter = Box::pin(pool.acquire());
+        tokio::select! {
+            biased;
+            _ = &mut waiter => unreachable!(),
+            _ = tokio::task::yield_now() => {}
+        }
+
+        // The returned slot is removed by the shrink instead of being handed over.
+        pool.resize(2);
+        drop(single);
+        tokio::select! {
+            biased;
+            _ = &mut waiter => unreachable!(),
+            _ = tokio::task::yield_now() => {}
+        }
+        assert_eq!(pool.status().in_use, 1);
+        drop(heavy);
+        assert!(waiter.await.is_ok());
+        assert_eq!(pool.size(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_status() {
+        let pool = Pool::new(Manager::default(), 2);
+        let obj = pool.acquire().await.unwrap();
+        let status = pool.status();
+        assert_eq!(
+            (status.total_created, status.idle, status.in_use),
+            (1, 0, 1)
+        );
+
+        drop(obj);
+        let status = pool.status();
+        assert_eq!(
+            (status.total_created, status.idle, status.in_use),
+            (1, 1, 0)
+        );
+
+        Pooled::take(pool.acquire().await.unwrap());
+        let status = pool.status();
+        assert_eq!(
+            (status.total_created, status.idle, status.in_use),
+            (0, 0, 0)
+        );
+
+        let counters = pool.counters();
+        assert_eq!((counters.acquires, counters.creations), (2, 1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_min_idle() {
+        let pool = Pool::new(Counter(AtomicUsize::new(0)), 3);
+        pool.set_min_idle(2);
+        tokio::time::advance(Duration::from_millis(10)).await;
+        assert_eq!(pool.manager().0.load(Ordering::SeqCst), 2);
+
+        // Taking a resource out of the pool triggers a replacement.
+        Pooled::take(pool.acquire().await.unwrap());
+        tokio::time::advance(Duration::from_millis(10)).await;
+        assert_eq!(pool.manager().0.load(Ordering::SeqCst), 3);
+
+        // Only the last free slot is refilled while two resources are in use.
+        let a = pool.try_acquire_idle_only().await.unwrap();
+        let b = pool.try_acquire_idle_only().await.unwrap();
+        assert_eq!((*a, *b), (1, 2));
+        tokio::time::advance(Duration::from_millis(10)).await;
+        assert_eq!(pool.manager().0.load(Ordering::SeqCst), 4);
+        assert_eq!(pool.status().idle, 1);
+
+        drop((a, b));
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert_eq!(pool.manager().0.load(Ordering::SeqCst), 4);
+        assert_eq!(pool.status().idle, 3);
+    }
 }
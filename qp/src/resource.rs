@@ -1,11 +1,16 @@
 //! A module for managed resources.
 use async_trait::async_trait;
+use tokio::time::Instant;
 
 /// An interface for managing resources used by [`Pool`](crate::Pool).
+///
+/// The manager and its resources must be `'static` because the pool destroys discarded resources
+/// and replenishes idle ones on spawned tasks. A manager which needs borrowed data should hold it
+/// in an [`Arc`](std::sync::Arc) instead.
 #[async_trait]
-pub trait Manage: Sync {
+pub trait Manage: Send + Sync + 'static {
     /// The type of resource managed by [`Pool`](crate::Pool).
-    type Output: Send + Sync;
+    type Output: Send + Sync + 'static;
 
     /// The type of error from a resource.
     type Error;
@@ -13,8 +18,60 @@ pub trait Manage: Sync {
     /// Tries to create a resource using the manager.
     async fn try_create(&self) -> Result<Self::Output, Self::Error>;
 
+    /// Resets a resource returned to the pool before it is acquired again.
+    ///
+    /// The resource is discarded if this returns an error.
+    async fn recycle(&self, _resource: &mut Self::Output) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Checks whether the given resource is still usable, returning the reason if it is not.
+    ///
+    /// The pool calls this before [`validate`](Manage::validate) and discards the resource if
+    /// either of them fails. The error is passed to the hooks as
+    /// [`Event::CheckFailed`](crate::Event::CheckFailed).
+    async fn check(&self, _resource: &Self::Output) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
     /// Returns `true` if the given resource is valid.
+    ///
+    /// Prefer [`check`](Manage::check), which reports why a resource is invalid.
     async fn validate(&self, _resource: &Self::Output) -> bool {
         true
     }
+
+    /// Destroys a resource discarded by the pool.
+    ///
+    /// This is called on a background task when a resource is expired, invalid, failed to be
+    /// recycled, or left idle in a dropped pool.
+    async fn destroy(&self, _resource: Self::Output) {}
+}
+
+/// Information about a resource managed by [`Pool`](crate::Pool).
+///
+/// This type is returned by the [`Pooled::metadata`](crate::Pooled::metadata) method.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Metadata {
+    /// The identifier of the resource, unique within its pool.
+    pub id: u64,
+    /// The time the resource was created.
+    pub created_at: Instant,
+    /// The time the resource was last acquired or returned to the pool.
+    pub last_used_at: Instant,
+    /// The number of times the resource has been acquired.
+    pub checkouts: u64,
+}
+
+impl Metadata {
+    pub(crate) fn new(id: u64) -> Self {
+        let now = Instant::now();
+        Self {
+            id,
+            created_at: now,
+            last_used_at: now,
+            checkouts: 0,
+        }
+    }
 }
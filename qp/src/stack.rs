@@ -0,0 +1,287 @@
+use crossbeam_epoch::{self as epoch, Atomic, Guard, Owned, Shared};
+use crossbeam_utils::Backoff;
+use std::mem::ManuallyDrop;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// The tag of a link held by [`Stack::remove_if`], which no other task may go through.
+const FROZEN: usize = 1;
+/// The tag of the link from a node to the next one while a task pops the node.
+const POPPED: usize = 2;
+
+/// An unbounded lock-free LIFO stack, the counterpart of [`crossbeam_queue::SegQueue`].
+///
+/// Popped nodes are reclaimed through `crossbeam_epoch`, so a concurrent `pop` never reads a
+/// freed node.
+pub(crate) struct Stack<T> {
+    head: Atomic<Node<T>>,
+    len: AtomicUsize,
+    removing: AtomicBool,
+}
+
+struct Node<T> {
+    value: ManuallyDrop<T>,
+    next: Atomic<Node<T>>,
+}
+
+unsafe impl<T: Send> Send for Stack<T> {}
+unsafe impl<T: Send> Sync for Stack<T> {}
+
+impl<T> Stack<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            head: Atomic::null(),
+            len: AtomicUsize::new(0),
+            removing: AtomicBool::new(false),
+        }
+    }
+
+    pub(crate) fn push(&self, value: T) {
+        // Count the node before it is visible, so `len` never underflows.
+        self.len.fetch_add(1, Ordering::Relaxed);
+        let mut node = Owned::new(Node {
+            value: ManuallyDrop::new(value),
+            next: Atomic::null(),
+        });
+        let guard = epoch::pin();
+        let backoff = Backoff::new();
+        loop {
+            let head = self.head.load(Ordering::Relaxed, &guard);
+            if head.tag() == FROZEN {
+                backoff.snooze();
+                continue;
+            }
+            node.next.store(head, Ordering::Relaxed);
+            match self.head.compare_exchange(
+                head,
+                node,
+                Ordering::Release,
+                Ordering::Relaxed,
+                &guard,
+            ) {
+                Ok(_) => break,
+                Err(err) => node = err.new,
+            }
+        }
+    }
+
+    pub(crate) fn pop(&self) -> Option<T> {
+        let guard = epoch::pin();
+        let backoff = Backoff::new();
+        loop {
+            let head = self.head.load(Ordering::Acquire, &guard);
+            if head.tag() == FROZEN {
+                backoff.snooze();
+                continue;
+            }
+            // SAFETY: the node cannot be reclaimed while the guard is pinned.
+            let node = unsafe { head.as_ref() }?;
+            let next = node.next.load(Ordering::Acquire, &guard);
+            if next.tag() != 0 {
+                backoff.snooze();
+                continue;
+            }
+            // Claim the link to the next node, so that `remove_if` cannot unlink that node before
+            // it becomes the head.
+            if node
+                .next
+                .compare_exchange(
+                    next,
+                    next.with_tag(POPPED),
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                    &guard,
+                )
+                .is_err()
+            {
+                continue;
+            }
+            if self
+                .head
+                .compare_exchange(head, next, Ordering::AcqRel, Ordering::Relaxed, &guard)
+                .is_ok()
+            {
+                self.len.fetch_sub(1, Ordering::Relaxed);
+                // SAFETY: the successful exchange unlinked the node, so only this thread takes the
+                // value out of it, and the node itself is freed without dropping the value.
+                unsafe {
+                    let value = ptr::read(&*node.value);
+                    guard.defer_destroy(head);
+                    return Some(value);
+                }
+            }
+            node.next.store(next, Ordering::Release);
+        }
+    }
+
+    /// Removes the values for which `f` returns `true`, from the top to the bottom, keeping the
+    /// others in place.
+    ///
+    /// The stack is walked while holding the link to the current node, so that concurrent pushes
+    /// and pops only wait while the walk passes the top of the stack. Returns no values if
+    /// another call is already running.
+    pub(crate) fn remove_if(&self, mut f: impl FnMut(&T) -> bool) -> Vec<T> {
+        let mut removed = Vec::new();
+        if self.removing.swap(true, Ordering::Acquire) {
+            return removed;
+        }
+        let guard = epoch::pin();
+        let mut link = &self.head;
+        let mut current = freeze(link, &guard);
+        // SAFETY: the node cannot be reclaimed while the guard is pinned, and it cannot be popped
+        // while the link to it is frozen.
+        while let Some(node) = unsafe { current.as_ref() } {
+            let next = freeze(&node.next, &guard);
+            if f(&node.value) {
+                // Skip the node, keeping the link frozen for the next one.
+                link.store(next.with_tag(FROZEN), Ordering::Release);
+                self.len.fetch_sub(1, Ordering::Relaxed);
+                // SAFETY: the node is no longer reachable, so only this thread takes the value out
+                // of it.
+                unsafe {
+                    removed.push(ptr::read(&*node.value));
+                    guard.defer_destroy(current);
+                }
+            } else {
+                link.store(current, Ordering::Release);
+                link = &node.next;
+            }
+            current = next;
+        }
+        link.store(current, Ordering::Release);
+        self.removing.store(false, Ordering::Release);
+        removed
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+}
+
+impl<T> Drop for Stack<T> {
+    fn drop(&mut self) {
+        // SAFETY: no other thread can access the stack anymore.
+        unsafe {
+            let guard = epoch::unprotected();
+            let mut head = self.head.load(Ordering::Relaxed, guard);
+            while let Some(node) = head.as_ref() {
+                let next = node.next.load(Ordering::Relaxed, guard);
+                let mut node = head.into_owned();
+                ManuallyDrop::drop(&mut node.value);
+                head = next;
+            }
+        }
+    }
+}
+
+/// Tags `link` as frozen once no other task goes through it, returning the node it points to.
+fn freeze<'g, T>(link: &Atomic<Node<T>>, guard: &'g Guard) -> Shared<'g, Node<T>> {
+    let backoff = Backoff::new();
+    loop {
+        let current = link.load(Ordering::Acquire, guard);
+        if current.tag() == 0
+            && link
+                .compare_exchange(
+                    current,
+                    current.with_tag(FROZEN),
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                    guard,
+                )
+                .is_ok()
+        {
+            return current;
+        }
+        backoff.snooze();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_stack() {
+        let stack = Stack::new();
+        for i in 0..3 {
+            stack.push(i);
+        }
+        assert_eq!(stack.len(), 3);
+        assert_eq!(stack.pop(), Some(2));
+        assert_eq!(stack.pop(), Some(1));
+        stack.push(3);
+        assert_eq!(stack.pop(), Some(3));
+        assert_eq!(stack.pop(), Some(0));
+        assert_eq!(stack.pop(), None);
+        assert_eq!(stack.len(), 0);
+
+        let stack = Arc::new(Stack::new());
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let stack = stack.clone();
+                std::thread::spawn(move || {
+                    for i in 0..1000 {
+                        stack.push(Box::new(i));
+                        assert!(stack.pop().is_some());
+                    }
+                    stack.push(Box::new(0));
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(stack.len(), 4);
+    }
+
+    #[test]
+    fn test_remove_if() {
+        let stack = Stack::new();
+        for i in 0..6 {
+            stack.push(i);
+        }
+        assert_eq!(stack.remove_if(|i| i % 2 == 0), vec![4, 2, 0]);
+        assert_eq!(stack.len(), 3);
+        stack.push(6);
+        assert_eq!(stack.remove_if(|i| *i == 6), vec![6]);
+        assert_eq!(stack.pop(), Some(5));
+        assert_eq!(stack.pop(), Some(3));
+        assert_eq!(stack.pop(), Some(1));
+        assert_eq!(stack.pop(), None);
+
+        // Every value is either popped or removed exactly once.
+        let stack = Arc::new(Stack::new());
+        let finished = Arc::new(AtomicUsize::new(0));
+        let threads: Vec<_> = (0..4)
+            .map(|thread| {
+                let stack = stack.clone();
+                let finished = finished.clone();
+                std::thread::spawn(move || {
+                    let mut popped = Vec::new();
+                    for i in 0..1000 {
+                        stack.push(thread * 1000 + i);
+                        if i % 2 == 0 {
+                            popped.extend(stack.pop());
+                        }
+                    }
+                    finished.fetch_add(1, Ordering::SeqCst);
+                    popped
+                })
+            })
+            .collect();
+        let mut seen = Vec::new();
+        while finished.load(Ordering::SeqCst) < 4 {
+            seen.extend(stack.remove_if(|i| i % 3 == 0));
+        }
+        for thread in threads {
+            seen.extend(thread.join().unwrap());
+        }
+        while let Some(i) = stack.pop() {
+            seen.push(i);
+        }
+        seen.sort_unstable();
+        assert_eq!(seen, (0..4000).collect::<Vec<_>>());
+        assert_eq!(stack.len(), 0);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::loom::{AtomicU64, AtomicUsize};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+/// A snapshot of the state of [`Pool`](crate::Pool).
+///
+/// This type is created by the [`Pool::status`](crate::Pool::status) method.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PoolStatus {
+    /// The maximum number of resources the pool can manage.
+    pub max_size: usize,
+    /// The number of resources created and not yet discarded.
+    pub total_created: usize,
+    /// The number of idle resources.
+    pub idle: usize,
+    /// The number of acquired resources.
+    pub in_use: usize,
+    /// The number of tasks waiting for a resource.
+    pub waiters: usize,
+    /// The state of the circuit breaker around resource creation.
+    pub breaker: BreakerState,
+}
+
+/// The state of the circuit breaker of [`Pool`](crate::Pool).
+///
+/// See [`CircuitBreaker`](crate::CircuitBreaker).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BreakerState {
+    /// Resources are created as usual.
+    Closed,
+    /// Resource creations fail immediately until the cool-down has elapsed.
+    Open,
+    /// A probe creation is in progress.
+    HalfOpen,
+}
+
+/// Cumulative counters of [`Pool`](crate::Pool) since it was created.
+///
+/// This type is created by the [`Pool::counters`](crate::Pool::counters) method.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PoolCounters {
+    /// The number of acquired resources.
+    pub acquires: u64,
+    /// The number of created resources.
+    pub creations: u64,
+    /// The number of failed resource creations.
+    pub creation_failures: u64,
+    /// The number of resources which failed validation.
+    pub validation_failures: u64,
+    /// The number of acquisitions which timed out.
+    pub timeouts: u64,
+    /// The total duration tasks waited for a free slot in the pool.
+    pub wait_time: Duration,
+}
+
+#[derive(Default)]
+pub(crate) struct Stats {
+    pub(crate) total_created: AtomicUsize,
+    pub(crate) in_use: AtomicUsize,
+    pub(crate) waiters: AtomicUsize,
+    pub(crate) acquires: AtomicU64,
+    pub(crate) creations: AtomicU64,
+    pub(crate) creation_failures: AtomicU64,
+    pub(crate) validation_failures: AtomicU64,
+    pub(crate) timeouts: AtomicU64,
+    wait_time: AtomicU64,
+}
+
+impl Stats {
+    pub(crate) fn incr(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_wait_time(&self, duration: Duration) {
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        self.wait_time.fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    /// Counts the caller as a waiter until the returned guard is dropped.
+    pub(crate) fn wait(&self) -> Waiting<'_> {
+        self.waiters.fetch_add(1, Ordering::Relaxed);
+        Waiting(&self.waiters)
+    }
+
+    pub(crate) fn counters(&self) -> PoolCounters {
+        PoolCounters {
+            acquires: self.acquires.load(Ordering::Relaxed),
+            creations: self.creations.load(Ordering::Relaxed),
+            creation_failures: self.creation_failures.load(Ordering::Relaxed),
+            validation_failures: self.validation_failures.load(Ordering::Relaxed),
+            timeouts: self.timeouts.load(Ordering::Relaxed),
+            wait_time: Duration::from_nanos(self.wait_time.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+pub(crate) struct Waiting<'a>(&'a AtomicUsize);
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -1,15 +1,42 @@
 //! Synchronization primitives for use in asynchronous contexts.
-use crossbeam_queue::SegQueue;
+use crate::loom::{AtomicBool, AtomicUsize, SegQueue, UnsafeCell};
 use crossbeam_utils::Backoff;
+use std::error::Error;
+use std::fmt;
 use std::future::Future;
 use std::pin::Pin;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 use std::task::{Context, Poll, Waker};
 
+/// The number of cancelled waiters tolerated in the queue before they are unlinked eagerly.
+const PURGE_THRESHOLD: usize = 32;
+
 /// Counting semaphore performing asynchronous permit acquisition.
+///
+/// Waiting tasks are queued in FIFO order, and a released permit is handed directly to the oldest
+/// one. A semaphore created by [`Semaphore::new`] still lets [`Semaphore::try_acquire`], newly
+/// arriving tasks and waiters for fewer permits take available permits while others are waiting,
+/// while one created by [`Semaphore::new_fair`] never lets them overtake a waiter.
 pub struct Semaphore {
     permits: AtomicUsize,
-    waiters: SegQueue<Waker>,
+    /// The number of permits of the semaphore, including the acquired ones.
+    total: AtomicUsize,
+    closed: AtomicBool,
+    fair: bool,
+    /// The number of waiters which are neither granted nor cancelled.
+    queued: AtomicUsize,
+    /// Newly registered waiters.
+    waiters: SegQueue<Arc<Waiter>>,
+    /// The waiters moved out of `waiters` when cancelled ones are unlinked. Only the dispatching
+    /// thread accesses `head` and `queue`, and the waiters in them precede those in `waiters`.
+    queue: SegQueue<Arc<Waiter>>,
+    /// The oldest waiter, which has been popped but not granted a permit yet.
+    head: SegQueue<Arc<Waiter>>,
+    /// The number of pending requests to grant permits to waiters.
+    dispatching: AtomicUsize,
+    /// The number of permits to be forgotten as they are released.
+    debt: AtomicUsize,
 }
 
 impl Semaphore {
@@ -21,16 +48,67 @@ impl Semaphore {
     /// # use qp::sync::Semaphore;
     /// let binary_semaphore = Semaphore::new(1);
     /// ```
+    #[cfg(not(loom))]
     pub const fn new(permits: usize) -> Self {
+        Self::with_fairness(permits, false)
+    }
+
+    /// Creates a new fair semaphore with the initial number of permits.
+    ///
+    /// A fair semaphore hands out permits strictly in the order they were requested: while any
+    /// task is waiting, [`Semaphore::try_acquire`] fails and new acquisitions queue up behind it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use qp::sync::Semaphore;
+    /// # use std::sync::Arc;
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let semaphore = Arc::new(Semaphore::new_fair(1));
+    /// let permit = semaphore.acquire().await.unwrap();
+    /// let waiter = tokio::spawn({
+    ///     let semaphore = semaphore.clone();
+    ///     async move { drop(semaphore.acquire().await.unwrap()) }
+    /// });
+    /// tokio::task::yield_now().await;
+    /// drop(permit);
+    /// // The permit is reserved for the waiting task.
+    /// assert!(semaphore.try_acquire().is_none());
+    /// waiter.await.unwrap();
+    /// # }
+    /// ```
+    #[cfg(not(loom))]
+    pub const fn new_fair(permits: usize) -> Self {
+        Self::with_fairness(permits, true)
+    }
+
+    #[cfg(not(loom))]
+    const fn with_fairness(permits: usize, fair: bool) -> Self {
         debug_assert!(permits >= 1);
         Self {
             permits: AtomicUsize::new(permits),
+            total: AtomicUsize::new(permits),
+            closed: AtomicBool::new(false),
+            fair,
+            queued: AtomicUsize::new(0),
             waiters: SegQueue::new(),
+            queue: SegQueue::new(),
+            head: SegQueue::new(),
+            dispatching: AtomicUsize::new(0),
+            debt: AtomicUsize::new(0),
         }
     }
 
+    /// Returns `true` if the semaphore was created by [`Semaphore::new_fair`].
+    pub fn is_fair(&self) -> bool {
+        self.fair
+    }
+
     /// Acquires a permit from the semaphore.
     ///
+    /// Fails with [`AcquireError::Closed`] if the semaphore is closed.
+    ///
     /// # Examples
     ///
     /// ```
@@ -39,14 +117,65 @@ impl Semaphore {
     /// # async fn main() {
     /// let binary_semaphore = Semaphore::new(1);
     /// assert_eq!(binary_semaphore.available_permits(), 1);
-    /// let permit = binary_semaphore.acquire().await;
+    /// let permit = binary_semaphore.acquire().await.unwrap();
     /// assert_eq!(binary_semaphore.available_permits(), 0);
     /// drop(permit);
     /// assert_eq!(binary_semaphore.available_permits(), 1);
     /// # }
     /// ```
-    pub async fn acquire(&self) -> SemaphorePermit<'_> {
-        Acquire::new(self).await
+    pub async fn acquire(&self) -> Result<SemaphorePermit<'_>, AcquireError> {
+        Acquire::new(self, 1).await
+    }
+
+    /// Acquires `n` permits from the semaphore at once.
+    ///
+    /// The permits are released together when the returned permit is dropped. Fails with
+    /// [`AcquireError::TooLarge`] if `n` is greater than the total number of permits of the
+    /// semaphore, or becomes so while waiting because permits were forgotten.
+    ///
+    /// On a fair semaphore, a task waiting for more permits than available holds up every task
+    /// queued behind it. On an unfair one, the tasks behind it which need fewer permits go first,
+    /// so it may wait for a long time under contention.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use qp::sync::Semaphore;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let semaphore = Semaphore::new(4);
+    /// let permit = semaphore.acquire_many(3).await.unwrap();
+    /// assert_eq!(permit.num_permits(), 3);
+    /// assert_eq!(semaphore.available_permits(), 1);
+    /// drop(permit);
+    /// assert_eq!(semaphore.available_permits(), 4);
+    /// # }
+    /// ```
+    pub async fn acquire_many(&self, n: usize) -> Result<SemaphorePermit<'_>, AcquireError> {
+        Acquire::new(self, n).await
+    }
+
+    /// Acquires an owned permit from the semaphore.
+    ///
+    /// The returned permit holds the semaphore by [`Arc`], so it can be moved into other tasks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use qp::sync::Semaphore;
+    /// # use std::sync::Arc;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let binary_semaphore = Arc::new(Semaphore::new(1));
+    /// let permit = binary_semaphore.clone().acquire_owned().await.unwrap();
+    /// assert_eq!(binary_semaphore.available_permits(), 0);
+    /// tokio::spawn(async move { drop(permit) }).await.unwrap();
+    /// assert_eq!(binary_semaphore.available_permits(), 1);
+    /// # }
+    /// ```
+    pub async fn acquire_owned(self: Arc<Self>) -> Result<OwnedSemaphorePermit, AcquireError> {
+        let permit = Acquire::new(&self, 1).await?;
+        Ok(permit.into_owned(self.clone()))
     }
 
     /// Returns the current number of available permits.
@@ -59,7 +188,7 @@ impl Semaphore {
     /// # async fn main() {
     /// let binary_semaphore = Semaphore::new(1);
     /// assert_eq!(binary_semaphore.available_permits(), 1);
-    /// let permit = binary_semaphore.acquire().await;
+    /// let permit = binary_semaphore.acquire().await.unwrap();
     /// assert_eq!(binary_semaphore.available_permits(), 0);
     /// # }
     /// ```
@@ -69,7 +198,8 @@ impl Semaphore {
 
     /// Tries to acquire a permit from the semaphore if there is one available.
     ///
-    /// Returns `None` immediately if there are no idle resources available in the pool.
+    /// Returns `None` immediately if there are no idle resources available in the pool, if the
+    /// semaphore is fair and other tasks are waiting for a permit, or if the semaphore is closed.
     ///
     /// # Examples
     ///
@@ -86,25 +216,366 @@ impl Semaphore {
     /// let permit3 = binary_semaphore.try_acquire();
     /// assert!(permit3.is_some());
     /// # }
-    pub fn try_acquire(&self) -> Option<SemaphorePermit> {
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_>> {
+        self.try_acquire_many(1)
+    }
+
+    /// Tries to acquire `n` permits from the semaphore at once if they are available.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use qp::sync::Semaphore;
+    /// let semaphore = Semaphore::new(4);
+    /// let permit1 = semaphore.try_acquire_many(3);
+    /// assert!(permit1.is_some());
+    /// let permit2 = semaphore.try_acquire_many(2);
+    /// assert!(permit2.is_none());
+    /// ```
+    pub fn try_acquire_many(&self, n: usize) -> Option<SemaphorePermit<'_>> {
+        if self.is_closed() || (self.fair && self.queued.load(Ordering::SeqCst) > 0) {
+            return None;
+        }
+        self.take(n).then(|| SemaphorePermit::new(self, n))
+    }
+
+    /// Tries to acquire an owned permit from the semaphore if there is one available.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use qp::sync::Semaphore;
+    /// # use std::sync::Arc;
+    /// let binary_semaphore = Arc::new(Semaphore::new(1));
+    /// let permit1 = binary_semaphore.clone().try_acquire_owned();
+    /// assert!(permit1.is_some());
+    /// let permit2 = binary_semaphore.clone().try_acquire_owned();
+    /// assert!(permit2.is_none());
+    /// ```
+    pub fn try_acquire_owned(self: Arc<Self>) -> Option<OwnedSemaphorePermit> {
+        let permit = self.try_acquire()?;
+        Some(permit.into_owned(self.clone()))
+    }
+
+    fn take(&self, n: usize) -> bool {
         let backoff = Backoff::new();
         let mut permits = self.permits.load(Ordering::Relaxed);
         loop {
-            if permits == 0 {
-                return None;
+            if permits < n {
+                return false;
             }
             match self.permits.compare_exchange_weak(
                 permits,
-                permits - 1,
-                Ordering::Acquire,
+                permits - n,
+                Ordering::SeqCst,
                 Ordering::Relaxed,
             ) {
-                Ok(_) => return Some(SemaphorePermit::new(self)),
+                Ok(_) => return true,
                 Err(changed) => permits = changed,
             }
             backoff.spin();
         }
     }
+
+    /// Adds `n` permits to the semaphore.
+    ///
+    /// The added permits are handed to the waiting tasks first, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use qp::sync::Semaphore;
+    /// let semaphore = Semaphore::new(1);
+    /// semaphore.add_permits(2);
+    /// assert_eq!(semaphore.available_permits(), 3);
+    /// ```
+    pub fn add_permits(&self, n: usize) {
+        self.total.fetch_add(n, Ordering::SeqCst);
+        self.release(n);
+    }
+
+    /// Closes the semaphore.
+    ///
+    /// All pending and future acquisitions fail with [`AcquireError::Closed`], and
+    /// [`Semaphore::try_acquire`] always returns `None`. Permits which have already been acquired
+    /// are not affected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use qp::sync::{AcquireError, Semaphore};
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let semaphore = Semaphore::new(1);
+    /// let permit = semaphore.acquire().await.unwrap();
+    /// semaphore.close();
+    /// assert!(matches!(semaphore.acquire().await, Err(AcquireError::Closed)));
+    /// assert!(semaphore.try_acquire().is_none());
+    /// # }
+    /// ```
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.dispatch();
+    }
+
+    /// Returns `true` if the semaphore has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// Returns `true` if any task is waiting for permits.
+    pub(crate) fn has_waiters(&self) -> bool {
+        self.queued.load(Ordering::SeqCst) > 0
+    }
+
+    /// Returns `true` if removed permits are still to be taken from the released ones.
+    pub(crate) fn has_debt(&self) -> bool {
+        self.debt.load(Ordering::SeqCst) > 0
+    }
+
+    /// Removes `n` permits from the semaphore.
+    ///
+    /// Available permits are removed immediately, and the rest are removed as they are released.
+    /// Waiters for more permits than remain fail with [`AcquireError::TooLarge`].
+    pub(crate) fn remove_permits(&self, n: usize) {
+        self.shrink(n);
+        self.debt.fetch_add(n, Ordering::SeqCst);
+        loop {
+            let available = self.permits.load(Ordering::SeqCst);
+            if available == 0 {
+                return;
+            }
+            let owed = self.settle(available);
+            if owed == 0 {
+                return;
+            }
+            let taken = self.take_up_to(owed);
+            if taken < owed {
+                self.debt.fetch_add(owed - taken, Ordering::SeqCst);
+            }
+            if taken == 0 {
+                return;
+            }
+        }
+    }
+
+    /// Reduces the total number of permits by `n`, failing the waiters which no longer fit.
+    fn shrink(&self, n: usize) {
+        self.total.fetch_sub(n, Ordering::SeqCst);
+        if self.queued.fetch_add(0, Ordering::SeqCst) > 0 {
+            self.dispatch();
+        }
+    }
+
+    /// Pays off the debt with up to `n` permits, returning the number of permits used.
+    fn settle(&self, n: usize) -> usize {
+        let mut debt = self.debt.load(Ordering::SeqCst);
+        loop {
+            let paid = debt.min(n);
+            if paid == 0 {
+                return 0;
+            }
+            match self.debt.compare_exchange_weak(
+                debt,
+                debt - paid,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => return paid,
+                Err(changed) => debt = changed,
+            }
+        }
+    }
+
+    fn take_up_to(&self, n: usize) -> usize {
+        let mut permits = self.permits.load(Ordering::SeqCst);
+        loop {
+            let taken = permits.min(n);
+            if taken == 0 {
+                return 0;
+            }
+            match self.permits.compare_exchange_weak(
+                permits,
+                permits - taken,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => return taken,
+                Err(changed) => permits = changed,
+            }
+        }
+    }
+
+    fn release(&self, mut n: usize) {
+        if self.debt.load(Ordering::SeqCst) > 0 {
+            n -= self.settle(n);
+            if n == 0 {
+                return;
+            }
+        }
+        self.permits.fetch_add(n, Ordering::SeqCst);
+        // A read-modify-write is ordered against the one in `enqueue`, so either this thread sees
+        // the new waiter or the waiter's own dispatch sees the released permits.
+        if self.queued.fetch_add(0, Ordering::SeqCst) > 0 {
+            self.dispatch();
+        }
+    }
+
+    fn enqueue(&self, waiter: Arc<Waiter>) {
+        self.queued.fetch_add(1, Ordering::SeqCst);
+        self.waiters.push(waiter);
+        self.dispatch();
+    }
+
+    fn cancel(&self, waiter: &Waiter) {
+        match waiter
+            .state
+            .compare_exchange(WAITING, CANCELLED, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) => {
+                self.queued.fetch_sub(1, Ordering::SeqCst);
+                // The cancelled waiter may have been blocking the queue.
+                self.dispatch();
+            }
+            // Forward the permits granted to the cancelled waiter.
+            Err(GRANTED) => self.release(waiter.permits),
+            Err(_) => {}
+        }
+    }
+
+    /// Grants permits to the waiters in FIFO order.
+    ///
+    /// Only one thread grants permits at a time. Requests made while it is running are served by
+    /// that thread before it returns, so none of them is lost.
+    fn dispatch(&self) {
+        if self.dispatching.fetch_add(1, Ordering::AcqRel) != 0 {
+            return;
+        }
+        let mut requests = 1;
+        loop {
+            self.grant();
+            let pending = self.dispatching.fetch_sub(requests, Ordering::AcqRel);
+            if pending == requests {
+                return;
+            }
+            requests = pending - requests;
+        }
+    }
+
+    fn grant(&self) {
+        let linked = self.head.len() + self.queue.len() + self.waiters.len();
+        if linked > 2 * self.queued.load(Ordering::SeqCst) + PURGE_THRESHOLD {
+            self.purge();
+        }
+        // The waiters which need more permits than available, in the order they were popped.
+        let mut blocked = Vec::new();
+        while let Some(waiter) = self.pop() {
+            if waiter.state.load(Ordering::Acquire) != WAITING {
+                continue;
+            }
+            if self.is_closed() {
+                if waiter
+                    .state
+                    .compare_exchange(WAITING, CLOSED, Ordering::AcqRel, Ordering::Acquire)
+                    .is_ok()
+                {
+                    self.queued.fetch_sub(1, Ordering::SeqCst);
+                    waiter.waker.wake();
+                }
+                continue;
+            }
+            if waiter.permits > self.total.load(Ordering::SeqCst) {
+                if waiter
+                    .state
+                    .compare_exchange(WAITING, TOO_LARGE, Ordering::AcqRel, Ordering::Acquire)
+                    .is_ok()
+                {
+                    self.queued.fetch_sub(1, Ordering::SeqCst);
+                    waiter.waker.wake();
+                }
+                continue;
+            }
+            if !self.take(waiter.permits) {
+                blocked.push(waiter);
+                // An unfair semaphore lets smaller requests overtake the blocked waiter.
+                if self.fair || self.permits.load(Ordering::SeqCst) == 0 {
+                    break;
+                }
+                continue;
+            }
+            if waiter
+                .state
+                .compare_exchange(WAITING, GRANTED, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+            {
+                self.queued.fetch_sub(1, Ordering::SeqCst);
+                waiter.waker.wake();
+            } else {
+                // The waiter has been cancelled in the meantime.
+                self.permits.fetch_add(waiter.permits, Ordering::SeqCst);
+            }
+        }
+        if !blocked.is_empty() {
+            // Put the blocked waiters back ahead of the others.
+            while let Some(waiter) = self.head.pop() {
+                blocked.push(waiter);
+            }
+            for waiter in blocked {
+                self.head.push(waiter);
+            }
+        }
+    }
+
+    fn pop(&self) -> Option<Arc<Waiter>> {
+        self.head
+            .pop()
+            .or_else(|| self.queue.pop())
+            .or_else(|| self.waiters.pop())
+    }
+
+    /// Unlinks all cancelled waiters, keeping the order of the others.
+    fn purge(&self) {
+        for _ in 0..self.queue.len() {
+            if let Some(waiter) = self.queue.pop() {
+                if waiter.state.load(Ordering::Acquire) == WAITING {
+                    self.queue.push(waiter);
+                }
+            }
+        }
+        while let Some(waiter) = self.waiters.pop() {
+            if waiter.state.load(Ordering::Acquire) == WAITING {
+                self.queue.push(waiter);
+            }
+        }
+    }
+}
+
+// The atomics of `loom` cannot be created in const contexts.
+#[cfg(loom)]
+impl Semaphore {
+    pub fn new(permits: usize) -> Self {
+        Self::with_fairness(permits, false)
+    }
+
+    pub fn new_fair(permits: usize) -> Self {
+        Self::with_fairness(permits, true)
+    }
+
+    fn with_fairness(permits: usize, fair: bool) -> Self {
+        debug_assert!(permits >= 1);
+        Self {
+            permits: AtomicUsize::new(permits),
+            total: AtomicUsize::new(permits),
+            closed: AtomicBool::new(false),
+            fair,
+            queued: AtomicUsize::new(0),
+            waiters: SegQueue::new(),
+            queue: SegQueue::new(),
+            head: SegQueue::new(),
+            dispatching: AtomicUsize::new(0),
+            debt: AtomicUsize::new(0),
+        }
+    }
 }
 
 /// A permit from the semaphore.
@@ -112,53 +583,266 @@ impl Semaphore {
 /// This type is created by the [`Semaphore::acquire`] method and related methods.
 pub struct SemaphorePermit<'a> {
     semaphore: &'a Semaphore,
+    permits: usize,
 }
 
 impl Drop for SemaphorePermit<'_> {
     fn drop(&mut self) {
-        self.semaphore.permits.fetch_add(1, Ordering::Release);
-        if let Some(waker) = self.semaphore.waiters.pop() {
-            waker.wake();
-        }
+        self.semaphore.release(self.permits);
     }
 }
 
 impl<'a> SemaphorePermit<'a> {
-    const fn new(semaphore: &'a Semaphore) -> Self {
-        Self { semaphore }
+    pub(crate) const fn new(semaphore: &'a Semaphore, permits: usize) -> Self {
+        Self { semaphore, permits }
+    }
+
+    /// Keeps the permits acquired when this permit is dropped, so that another task can take
+    /// them over with [`SemaphorePermit::new`].
+    pub(crate) fn disarm(&mut self) {
+        self.permits = 0;
+    }
+
+    /// Returns the number of permits held by this permit.
+    pub fn num_permits(&self) -> usize {
+        self.permits
+    }
+
+    /// Forgets the permit without releasing it back to the semaphore.
+    ///
+    /// This permanently reduces the number of permits of the semaphore.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use qp::sync::Semaphore;
+    /// let semaphore = Semaphore::new(2);
+    /// semaphore.try_acquire().unwrap().forget();
+    /// assert_eq!(semaphore.available_permits(), 1);
+    /// ```
+    pub fn forget(self) {
+        self.semaphore.shrink(self.permits);
+        std::mem::forget(self);
+    }
+
+    fn into_owned(self, semaphore: Arc<Semaphore>) -> OwnedSemaphorePermit {
+        let permits = self.permits;
+        std::mem::forget(self);
+        OwnedSemaphorePermit::new(semaphore, permits)
+    }
+}
+
+/// An owned permit from the semaphore.
+///
+/// This type is created by the [`Semaphore::acquire_owned`] method and related methods.
+pub struct OwnedSemaphorePermit {
+    semaphore: Arc<Semaphore>,
+    permits: usize,
+}
+
+impl Drop for OwnedSemaphorePermit {
+    fn drop(&mut self) {
+        self.semaphore.release(self.permits);
+    }
+}
+
+impl OwnedSemaphorePermit {
+    pub(crate) const fn new(semaphore: Arc<Semaphore>, permits: usize) -> Self {
+        Self { semaphore, permits }
+    }
+
+    /// Keeps the permits acquired when this permit is dropped, so that another task can take
+    /// them over with [`OwnedSemaphorePermit::new`].
+    pub(crate) fn disarm(&mut self) {
+        self.permits = 0;
+    }
+
+    /// Returns the number of permits held by this permit.
+    pub fn num_permits(&self) -> usize {
+        self.permits
+    }
+
+    /// Forgets the permit without releasing it back to the semaphore.
+    ///
+    /// This permanently reduces the number of permits of the semaphore.
+    pub fn forget(mut self) {
+        self.semaphore.shrink(self.permits);
+        self.permits = 0;
     }
 }
 
+/// An error returned from [`Semaphore::acquire`] and related methods.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AcquireError {
+    /// The semaphore has been closed.
+    Closed,
+    /// More permits were requested than the semaphore has in total.
+    TooLarge,
+}
+
+impl fmt::Display for AcquireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Closed => write!(f, "semaphore has been closed"),
+            Self::TooLarge => write!(f, "requested more permits than the semaphore has"),
+        }
+    }
+}
+
+impl Error for AcquireError {}
+
+const WAITING: usize = 0;
+const GRANTED: usize = 1;
+const CANCELLED: usize = 2;
+const CLOSED: usize = 3;
+const TOO_LARGE: usize = 4;
+
+struct Waiter {
+    permits: usize,
+    state: AtomicUsize,
+    waker: AtomicWaker,
+}
+
 struct Acquire<'a> {
     semaphore: &'a Semaphore,
-    waiting: AtomicBool,
+    permits: usize,
+    waiter: Option<Arc<Waiter>>,
 }
 
 impl<'a> Future for Acquire<'a> {
-    type Output = SemaphorePermit<'a>;
+    type Output = Result<SemaphorePermit<'a>, AcquireError>;
 
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        match self.semaphore.try_acquire() {
-            Some(permit) => Poll::Ready(permit),
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let semaphore = self.semaphore;
+        match &self.waiter {
+            // The task may have moved to another executor since the last poll, so the waker is
+            // registered again before checking the state.
+            Some(waiter) => waiter.waker.register(cx.waker()),
             None => {
-                if self
-                    .waiting
-                    .compare_exchange(false, true, Ordering::SeqCst, Ordering::Relaxed)
-                    .is_ok()
-                {
-                    self.semaphore.waiters.push(cx.waker().clone());
+                if semaphore.is_closed() {
+                    return Poll::Ready(Err(AcquireError::Closed));
+                }
+                if self.permits > semaphore.total.load(Ordering::SeqCst) {
+                    return Poll::Ready(Err(AcquireError::TooLarge));
                 }
-                Poll::Pending
+                if let Some(permit) = semaphore.try_acquire_many(self.permits) {
+                    return Poll::Ready(Ok(permit));
+                }
+                let waiter = Arc::new(Waiter {
+                    permits: self.permits,
+                    state: AtomicUsize::new(WAITING),
+                    waker: AtomicWaker::new(),
+                });
+                waiter.waker.register(cx.waker());
+                semaphore.enqueue(waiter.clone());
+                self.waiter = Some(waiter);
             }
         }
+        let state = self
+            .waiter
+            .as_ref()
+            .map_or(WAITING, |waiter| waiter.state.load(Ordering::Acquire));
+        match state {
+            GRANTED => {
+                self.waiter = None;
+                Poll::Ready(Ok(SemaphorePermit::new(semaphore, self.permits)))
+            }
+            CLOSED => {
+                self.waiter = None;
+                Poll::Ready(Err(AcquireError::Closed))
+            }
+            TOO_LARGE => {
+                self.waiter = None;
+                Poll::Ready(Err(AcquireError::TooLarge))
+            }
+            _ => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        if let Some(waiter) = self.waiter.take() {
+            self.semaphore.cancel(&waiter);
+        }
     }
 }
 
 impl<'a> Acquire<'a> {
-    const fn new(semaphore: &'a Semaphore) -> Self {
+    const fn new(semaphore: &'a Semaphore, permits: usize) -> Self {
         Self {
             semaphore,
-            waiting: AtomicBool::new(false),
+            permits,
+            waiter: None,
+        }
+    }
+}
+
+const IDLE: usize = 0;
+const REGISTERING: usize = 1;
+const WAKING: usize = 2;
+
+/// A waker slot which can be updated and woken concurrently.
+struct AtomicWaker {
+    state: AtomicUsize,
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+// The waker is only accessed by the thread which moved `state` out of `IDLE`.
+unsafe impl Send for AtomicWaker {}
+unsafe impl Sync for AtomicWaker {}
+
+impl AtomicWaker {
+    fn new() -> Self {
+        Self {
+            state: AtomicUsize::new(IDLE),
+            waker: UnsafeCell::new(None),
+        }
+    }
+
+    /// Stores `waker` to be woken by the next call to [`AtomicWaker::wake`].
+    ///
+    /// Must not be called concurrently with itself.
+    fn register(&self, waker: &Waker) {
+        match self
+            .state
+            .compare_exchange(IDLE, REGISTERING, Ordering::Acquire, Ordering::Acquire)
+        {
+            Ok(_) => {
+                // SAFETY: `REGISTERING` excludes every other access to the waker.
+                self.waker.with_mut(|slot| unsafe {
+                    if !(*slot).as_ref().map_or(false, |old| old.will_wake(waker)) {
+                        *slot = Some(waker.clone());
+                    }
+                });
+                if self
+                    .state
+                    .compare_exchange(REGISTERING, IDLE, Ordering::AcqRel, Ordering::Acquire)
+                    .is_err()
+                {
+                    // `wake` was called while registering and left the waking to this thread.
+                    // SAFETY: `wake` does not touch the waker while `REGISTERING` is set.
+                    let waker = self.waker.with_mut(|slot| unsafe { (*slot).take() });
+                    self.state.swap(IDLE, Ordering::AcqRel);
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                }
+            }
+            // `wake` is running and might miss the new waker.
+            Err(_) => waker.wake_by_ref(),
+        }
+    }
+
+    fn wake(&self) {
+        if self.state.fetch_or(WAKING, Ordering::AcqRel) == IDLE {
+            // SAFETY: `WAKING` excludes every other access to the waker.
+            let waker = self.waker.with_mut(|slot| unsafe { (*slot).take() });
+            self.state.fetch_and(!WAKING, Ordering::Release);
+            if let Some(waker) = waker {
+                waker.wake();
+            }
         }
     }
 }
@@ -204,4 +888,221 @@ mod tests {
         // Show memory leak.
         assert!(sem.waiters.is_empty());
     }
+
+    struct CountWaker(AtomicUsize);
+
+    impl std::task::Wake for CountWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn count_waker() -> (Arc<CountWaker>, Waker) {
+        let count = Arc::new(CountWaker(AtomicUsize::new(0)));
+        (count.clone(), Waker::from(count))
+    }
+
+    #[test]
+    fn test_cancel_acquire() {
+        let sem = Semaphore::new(1);
+        let permit = sem.try_acquire().unwrap();
+        let (_, waker) = count_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        // Cancelled waiters are unlinked instead of piling up in the queue.
+        for _ in 0..1000 {
+            let mut acquire = Acquire::new(&sem, 1);
+            assert!(Pin::new(&mut acquire).poll(&mut cx).is_pending());
+        }
+        assert!(sem.head.len() + sem.queue.len() + sem.waiters.len() <= PURGE_THRESHOLD + 1);
+
+        // A waiter dropped after being granted a permit forwards it to the next one.
+        let mut a = Acquire::new(&sem, 1);
+        let mut b = Acquire::new(&sem, 1);
+        assert!(Pin::new(&mut a).poll(&mut cx).is_pending());
+        assert!(Pin::new(&mut b).poll(&mut cx).is_pending());
+        drop(permit);
+        drop(a);
+        assert!(Pin::new(&mut b).poll(&mut cx).is_ready());
+        assert_eq!(sem.queued.load(Ordering::SeqCst), 0);
+        assert_eq!(sem.available_permits(), 1);
+    }
+
+    #[test]
+    fn test_update_waker() {
+        let sem = Semaphore::new(1);
+        let permit = sem.try_acquire().unwrap();
+        let (old, old_waker) = count_waker();
+        let (new, new_waker) = count_waker();
+
+        let mut acquire = Acquire::new(&sem, 1);
+        let mut cx = Context::from_waker(&old_waker);
+        assert!(Pin::new(&mut acquire).poll(&mut cx).is_pending());
+        let mut cx = Context::from_waker(&new_waker);
+        assert!(Pin::new(&mut acquire).poll(&mut cx).is_pending());
+
+        drop(permit);
+        assert_eq!(old.0.load(Ordering::SeqCst), 0);
+        assert_eq!(new.0.load(Ordering::SeqCst), 1);
+        assert!(Pin::new(&mut acquire).poll(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn test_acquire_many() {
+        let sem = Semaphore::new_fair(3);
+        let permit = sem.try_acquire_many(2).unwrap();
+        let (_, waker) = count_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        // The waiter for 2 permits blocks the one for a single permit queued behind it.
+        let mut many = Acquire::new(&sem, 2);
+        let mut one = Acquire::new(&sem, 1);
+        assert!(Pin::new(&mut many).poll(&mut cx).is_pending());
+        assert!(Pin::new(&mut one).poll(&mut cx).is_pending());
+        assert_eq!(sem.available_permits(), 1);
+
+        drop(permit);
+        let many = match Pin::new(&mut many).poll(&mut cx) {
+            Poll::Ready(permit) => permit.unwrap(),
+            Poll::Pending => panic!("permits were not granted"),
+        };
+        assert_eq!(many.num_permits(), 2);
+        let one = Pin::new(&mut one).poll(&mut cx);
+        assert!(one.is_ready());
+        assert_eq!(sem.available_permits(), 0);
+
+        // An unfair semaphore grants the waiter for a single permit first instead.
+        let sem = Semaphore::new(3);
+        let permit = sem.try_acquire_many(3).unwrap();
+        let mut many = Acquire::new(&sem, 2);
+        let mut one = Acquire::new(&sem, 1);
+        assert!(Pin::new(&mut many).poll(&mut cx).is_pending());
+        assert!(Pin::new(&mut one).poll(&mut cx).is_pending());
+        sem.add_permits(1);
+        let one = match Pin::new(&mut one).poll(&mut cx) {
+            Poll::Ready(permit) => permit.unwrap(),
+            Poll::Pending => panic!("permit was not granted"),
+        };
+        assert!(Pin::new(&mut many).poll(&mut cx).is_pending());
+        drop(one);
+        drop(permit);
+        assert!(Pin::new(&mut many).poll(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn test_remove_permits() {
+        let sem = Semaphore::new(3);
+        let permit = sem.try_acquire_many(2).unwrap();
+
+        // The available permit is removed at once, and the others as they are released.
+        sem.remove_permits(2);
+        assert_eq!(sem.available_permits(), 0);
+        drop(permit);
+        assert_eq!(sem.available_permits(), 1);
+
+        // Added permits pay off the remaining debt first.
+        sem.remove_permits(2);
+        sem.add_permits(2);
+        assert_eq!(sem.available_permits(), 1);
+
+        // A waiter for more permits than remain fails.
+        let sem = Semaphore::new(2);
+        let permit = sem.try_acquire().unwrap();
+        let (_, waker) = count_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut many = Acquire::new(&sem, 2);
+        assert!(Pin::new(&mut many).poll(&mut cx).is_pending());
+        permit.forget();
+        let many = Pin::new(&mut many).poll(&mut cx);
+        assert!(matches!(many, Poll::Ready(Err(AcquireError::TooLarge))));
+        assert!(matches!(
+            Pin::new(&mut Acquire::new(&sem, 2)).poll(&mut cx),
+            Poll::Ready(Err(AcquireError::TooLarge))
+        ));
+    }
+
+    #[test]
+    fn test_close() {
+        let sem = Semaphore::new(2);
+        let permit = sem.try_acquire_many(2).unwrap();
+        let (count, waker) = count_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut a = Acquire::new(&sem, 1);
+        let mut b = Acquire::new(&sem, 1);
+        assert!(Pin::new(&mut a).poll(&mut cx).is_pending());
+        assert!(Pin::new(&mut b).poll(&mut cx).is_pending());
+
+        // Closing wakes all waiters, which then fail.
+        sem.close();
+        assert_eq!(count.0.load(Ordering::SeqCst), 2);
+        assert!(matches!(
+            Pin::new(&mut a).poll(&mut cx),
+            Poll::Ready(Err(AcquireError::Closed))
+        ));
+        assert!(matches!(
+            Pin::new(&mut b).poll(&mut cx),
+            Poll::Ready(Err(AcquireError::Closed))
+        ));
+
+        // Acquired permits are still released, but cannot be acquired again.
+        drop(permit);
+        assert_eq!(sem.available_permits(), 2);
+        assert!(sem.try_acquire().is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_fair() {
+        const WAITERS: usize = 32;
+
+        let sem = Arc::new(Semaphore::new_fair(1));
+        let permit = sem.try_acquire().unwrap();
+
+        // Queue up the waiters one by one so that their arrival order is known.
+        let order = Arc::new(SegQueue::new());
+        let mut handles = Vec::new();
+        for i in 0..WAITERS {
+            handles.push(tokio::spawn({
+                let sem = sem.clone();
+                let order = order.clone();
+                async move {
+                    let _permit = sem.acquire().await;
+                    order.push(i);
+                    tokio::task::yield_now().await;
+                }
+            }));
+            while sem.queued.load(Ordering::SeqCst) <= i {
+                tokio::task::yield_now().await;
+            }
+        }
+
+        // Barging tasks hammer the semaphore while the waiters are served. None of them may get
+        // a permit before every queued waiter has had its turn.
+        let stolen = Arc::new(AtomicUsize::new(0));
+        let barging: Vec<_> = (0..3)
+            .map(|_| {
+                let sem = sem.clone();
+                let order = order.clone();
+                let stolen = stolen.clone();
+                tokio::spawn(async move {
+                    while order.len() < WAITERS {
+                        if sem.try_acquire().is_some() && order.len() < WAITERS {
+                            stolen.fetch_add(1, Ordering::SeqCst);
+                        }
+                        tokio::task::yield_now().await;
+                    }
+                })
+            })
+            .collect();
+
+        drop(permit);
+        for handle in handles.into_iter().chain(barging) {
+            handle.await.unwrap();
+        }
+
+        let order: Vec<_> = std::iter::from_fn(|| order.pop()).collect();
+        assert_eq!(order, (0..WAITERS).collect::<Vec<_>>());
+        assert_eq!(stolen.load(Ordering::SeqCst), 0);
+        assert_eq!(sem.available_permits(), 1);
+    }
 }
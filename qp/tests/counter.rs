@@ -58,7 +58,7 @@ async fn main() {
     for _ in 0..MAX_POOL_SIZE {
         let counter = pool.acquire().await.unwrap();
         sum += dbg!(counter.get());
-        drop(Pooled::take(counter));
+        let _ = Pooled::take(counter);
     }
     assert_eq!(sum as usize, WORKERS * ITERATIONS);
 }
@@ -0,0 +1,174 @@
+//! Model tests exploring the interleavings of the semaphore and the pool with `loom`.
+//!
+//! Run them with:
+//!
+//! ```sh
+//! RUSTFLAGS="--cfg loom" cargo test -p qp --release --test loom
+//! ```
+#![cfg(loom)]
+
+use async_trait::async_trait;
+use loom::future::block_on;
+use loom::sync::atomic::{AtomicUsize, Ordering};
+use loom::thread;
+use qp::resource::Manage;
+use qp::sync::{AcquireError, Semaphore};
+use qp::Pool;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Wake, Waker};
+
+struct Noop;
+
+impl Wake for Noop {
+    fn wake(self: Arc<Self>) {}
+}
+
+/// Polls the future once, so that it registers itself as a waiter.
+fn poll_once<F: Future + Unpin>(future: &mut F) -> bool {
+    let waker = Waker::from(Arc::new(Noop));
+    Pin::new(future)
+        .poll(&mut Context::from_waker(&waker))
+        .is_ready()
+}
+
+fn model(f: impl Fn() + Sync + Send + 'static) {
+    let mut builder = loom::model::Builder::new();
+    builder.preemption_bound = Some(3);
+    builder.check(f);
+}
+
+#[test]
+fn try_acquire_never_hands_out_a_permit_twice() {
+    model(|| {
+        let sem = Arc::new(Semaphore::new(1));
+        let held = Arc::new(AtomicUsize::new(0));
+        let threads: Vec<_> = (0..2)
+            .map(|_| {
+                let sem = sem.clone();
+                let held = held.clone();
+                thread::spawn(move || {
+                    if let Some(permit) = sem.try_acquire() {
+                        assert_eq!(held.fetch_add(1, Ordering::SeqCst), 0);
+                        held.fetch_sub(1, Ordering::SeqCst);
+                        drop(permit);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(sem.available_permits(), 1);
+    });
+}
+
+#[test]
+fn release_wakes_waiter() {
+    model(|| {
+        let sem = Arc::new(Semaphore::new(1));
+        let permit = sem.try_acquire().unwrap();
+        let waiter = {
+            let sem = sem.clone();
+            thread::spawn(move || drop(block_on(sem.acquire()).unwrap()))
+        };
+        drop(permit);
+        waiter.join().unwrap();
+        assert_eq!(sem.available_permits(), 1);
+    });
+}
+
+#[test]
+fn release_wakes_fair_waiters() {
+    model(|| {
+        let sem = Arc::new(Semaphore::new_fair(1));
+        let permit = sem.try_acquire().unwrap();
+        let waiters: Vec<_> = (0..2)
+            .map(|_| {
+                let sem = sem.clone();
+                thread::spawn(move || drop(block_on(sem.acquire()).unwrap()))
+            })
+            .collect();
+        drop(permit);
+        for waiter in waiters {
+            waiter.join().unwrap();
+        }
+        assert_eq!(sem.available_permits(), 1);
+    });
+}
+
+#[test]
+fn cancelled_waiter_forwards_permit() {
+    model(|| {
+        let sem = Arc::new(Semaphore::new(1));
+        let permit = sem.try_acquire().unwrap();
+        let cancelled = {
+            let sem = sem.clone();
+            thread::spawn(move || {
+                let mut acquire = Box::pin(sem.acquire());
+                if !poll_once(&mut acquire) {
+                    // Dropping the pending acquisition must not swallow a granted permit.
+                    drop(acquire);
+                }
+            })
+        };
+        let waiter = {
+            let sem = sem.clone();
+            thread::spawn(move || drop(block_on(sem.acquire()).unwrap()))
+        };
+        drop(permit);
+        cancelled.join().unwrap();
+        waiter.join().unwrap();
+        assert_eq!(sem.available_permits(), 1);
+    });
+}
+
+#[test]
+fn close_wakes_waiter() {
+    model(|| {
+        let sem = Arc::new(Semaphore::new(1));
+        let permit = sem.try_acquire().unwrap();
+        let waiter = {
+            let sem = sem.clone();
+            thread::spawn(move || block_on(sem.acquire()).map(drop))
+        };
+        sem.close();
+        assert_eq!(waiter.join().unwrap(), Err(AcquireError::Closed));
+        drop(permit);
+        assert_eq!(sem.available_permits(), 1);
+    });
+}
+
+struct Manager(AtomicUsize);
+
+#[async_trait]
+impl Manage for Manager {
+    type Output = usize;
+    type Error = ();
+
+    async fn try_create(&self) -> Result<Self::Output, Self::Error> {
+        Ok(self.0.fetch_add(1, Ordering::SeqCst))
+    }
+}
+
+#[test]
+fn returned_resource_is_reused() {
+    model(|| {
+        let pool = Pool::new(Manager(AtomicUsize::new(0)), 1);
+        let threads: Vec<_> = (0..2)
+            .map(|_| {
+                let pool = pool.clone();
+                thread::spawn(move || {
+                    let resource = block_on(pool.acquire()).unwrap();
+                    assert_eq!(*resource, 0);
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(pool.manager().0.load(Ordering::SeqCst), 1);
+        assert_eq!(pool.size(), 1);
+    });
+}